@@ -0,0 +1,26 @@
+//! An open file that isn't the one being edited right now, kept with everything needed to come back to it.
+
+use crate::document::{Document, Position};
+
+pub struct Buffer {
+    pub number: usize, // what `:b` and `:ls` call it, stays the same while the buffer is open
+    pub docu: Document, // the undo history lives in here, so every buffer keeps its own
+    pub cursor: Position,
+    pub top_offset: usize,
+    pub left_offset: usize,
+    pub has_unsaved_changes: bool,
+}
+
+impl Buffer {
+    /// a buffer that hasn't been looked at yet, with the cursor at the top
+    pub fn new(number: usize, docu: Document) -> Self {
+        Buffer {
+            number,
+            docu,
+            cursor: Position::default(),
+            top_offset: 0,
+            left_offset: 0,
+            has_unsaved_changes: false,
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! Command line arguments: the files to open, where to start in them and how to open them.
+
+use crate::document::Position;
+use std::path::Path;
+
+pub const USAGE: &str = "\
+Usage: pascal [options] [file ...]
+
+Options:
+  +<line>                 start at <line> in the first file, `+` alone starts at the last line
+  <file>:<line>[:<col>]   open <file> at <line> and <col>, as printed by compilers and grep
+  -R                      open the files read-only
+  -h, --help              print this help and exit
+  -V, --version           print the version and exit
+  --                      treat every argument after this one as a file";
+
+pub enum Cli {
+    Edit(Args),
+    Help,
+    Version,
+}
+
+#[derive(Default)]
+pub struct Args {
+    pub files: Vec<FileArg>, // no files opens an empty scratch buffer
+    pub read_only: bool,
+}
+
+pub struct FileArg {
+    pub path: String,
+    pub position: Option<Position>, // where the cursor starts, clamped to the file once it's open
+}
+
+impl Cli {
+    pub fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Args::default();
+        let mut start_line = None;
+        let mut only_files = false;
+        for arg in args {
+            if only_files {
+                parsed.files.push(FileArg::parse(arg));
+                continue;
+            }
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(Cli::Help),
+                "-V" | "--version" => return Ok(Cli::Version),
+                "-R" => parsed.read_only = true,
+                "--" => only_files = true,
+                "+" => start_line = Some(usize::MAX),
+                _ if arg.starts_with('+') => {
+                    let line = arg[1..]
+                        .parse::<usize>()
+                        .map_err(|_| format!("invalid line number: {arg}"))?;
+                    start_line = Some(line.saturating_sub(1));
+                }
+                _ if arg.starts_with('-') && arg.len() > 1 => {
+                    return Err(format!("unknown option: {arg}"));
+                }
+                _ => parsed.files.push(FileArg::parse(arg)),
+            }
+        }
+        // like in vim `+<line>` goes with the first file wherever it's written
+        if let Some(line) = start_line
+            && let Some(first) = parsed.files.first_mut()
+        {
+            first.position = Some(Position::new(line, 0));
+        }
+        Ok(Cli::Edit(parsed))
+    }
+}
+
+impl FileArg {
+    fn parse(arg: String) -> Self {
+        // a file that really is called `notes:12` opens as it is
+        if !Path::new(&arg).exists()
+            && let Some((path, position)) = split_location(&arg)
+        {
+            return FileArg {
+                path: path.to_string(),
+                position: Some(position),
+            };
+        }
+        FileArg {
+            path: arg,
+            position: None,
+        }
+    }
+}
+
+/// splits `file:line:col` and `file:line`, both 1-based, a trailing `:` as `grep -n` prints it is fine
+fn split_location(arg: &str) -> Option<(&str, Position)> {
+    let arg = arg.strip_suffix(':').unwrap_or(arg);
+    let (rest, last) = arg.rsplit_once(':')?;
+    let last = last.parse::<usize>().ok()?.saturating_sub(1);
+    if let Some((path, line)) = rest.rsplit_once(':')
+        && let Ok(line) = line.parse::<usize>()
+        && !path.is_empty()
+    {
+        return Some((path, Position::new(line.saturating_sub(1), last)));
+    }
+    (!rest.is_empty()).then_some((rest, Position::new(last, 0)))
+}
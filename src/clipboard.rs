@@ -0,0 +1,80 @@
+//! The system clipboard behind the `+` and `*` registers, `*` being the primary selection on X11 and Wayland.
+//! Copying goes through the terminal with OSC 52 so it works over ssh, and through xclip, wl-copy or pbcopy
+//! when one of them is installed since not every terminal supports OSC 52. Pasting needs one of the tools.
+
+use crate::term::Terminal;
+use std::io::{Error, Write};
+use std::process::{Command, Stdio};
+
+pub fn copy(text: &str, primary: bool) -> Result<(), Error> {
+    Terminal::copy_to_clipboard(text, primary)?;
+    // the terminal doesn't say whether it understood OSC 52, so a local tool gets the text too if there is one
+    let tools: [(&str, &[&str]); 3] = if primary {
+        [
+            ("wl-copy", &["--primary"]),
+            ("xclip", &["-selection", "primary"]),
+            ("pbcopy", &[]),
+        ]
+    } else {
+        [
+            ("wl-copy", &[]),
+            ("xclip", &["-selection", "clipboard"]),
+            ("pbcopy", &[]),
+        ]
+    };
+    for (program, args) in tools {
+        if pipe_to(program, args, text).is_ok() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// what's on the system clipboard, none when no tool to read it is installed or working
+pub fn paste(primary: bool) -> Option<String> {
+    let tools: [(&str, &[&str]); 3] = if primary {
+        [
+            ("wl-paste", &["--no-newline", "--primary"]),
+            ("xclip", &["-selection", "primary", "-o"]),
+            ("pbpaste", &[]),
+        ]
+    } else {
+        [
+            ("wl-paste", &["--no-newline"]),
+            ("xclip", &["-selection", "clipboard", "-o"]),
+            ("pbpaste", &[]),
+        ]
+    };
+    tools.into_iter().find_map(|(program, args)| {
+        let output = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        // the document only ever holds '\n' line breaks
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).replace("\r\n", "\n"))
+    })
+}
+
+/// runs `program` with `text` as its input, its output is thrown away so it can't end up on screen
+fn pipe_to(program: &str, args: &[&str], text: &str) -> Result<(), Error> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Error::other(format!("{program} failed with {status}")))
+    }
+}
@@ -0,0 +1,195 @@
+//! The command line on the bottom row of the screen and the ex-style commands typed into it.
+
+use crate::substitute::{LineRange, SubstituteArgs};
+use unicode_segmentation::UnicodeSegmentation;
+
+pub enum Command {
+    Write {
+        path: Option<String>,
+        force: bool,
+    },
+    Quit {
+        force: bool,
+    },
+    WriteQuit,
+    Edit {
+        path: Option<String>,
+        force: bool,
+    },
+    GotoLine(usize),
+    Set(String),
+    NoHighlight,
+    Substitute(SubstituteArgs),
+    BufferNext,
+    BufferPrevious,
+    Buffer(String), // a buffer number or part of its name
+    ListBuffers,
+    BufferDelete {
+        buffer: Option<String>,
+        force: bool,
+    },
+    Split {
+        path: Option<String>,
+        vertical: bool,
+    },
+    Close,
+    Only,
+    TabNew(Option<String>),
+    TabNext,
+    TabPrevious,
+    TabClose,
+}
+
+#[derive(Default)]
+pub struct CommandLine {
+    pub input: String,
+    pub cursor: usize, // in graphemes
+    history: Vec<String>,
+    history_idx: Option<usize>, // which history entry is being shown while browsing with up/down
+}
+
+impl Command {
+    pub fn parse(input: &str) -> Result<Command, String> {
+        let input = input.trim();
+        if let Ok(line) = input.parse::<usize>() {
+            return Ok(Command::GotoLine(line));
+        }
+        let (range, rest) = LineRange::parse(input)?;
+        let substitute = rest
+            .strip_prefix("substitute")
+            .or_else(|| rest.strip_prefix('s'))
+            .filter(|args| args.starts_with(|c: char| !c.is_alphanumeric() && !c.is_whitespace()));
+        if let Some(args) = substitute {
+            return SubstituteArgs::parse(range, args).map(Command::Substitute);
+        }
+        if !matches!(range, LineRange::Current) {
+            return Err(format!("Not an editor command: {input}"));
+        }
+        let (name, arg) = match input.split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, Some(arg.trim().to_string())),
+            None => (input, None),
+        };
+        match (name, arg) {
+            ("w" | "write", path) => Ok(Command::Write { path, force: false }),
+            ("w!" | "write!", path) => Ok(Command::Write { path, force: true }),
+            ("q" | "quit", None) => Ok(Command::Quit { force: false }),
+            ("q!" | "quit!", None) => Ok(Command::Quit { force: true }),
+            ("wq" | "x", None) => Ok(Command::WriteQuit),
+            ("e" | "edit", path) => Ok(Command::Edit { path, force: false }),
+            ("e!" | "edit!", path) => Ok(Command::Edit { path, force: true }),
+            ("set", Some(option)) => Ok(Command::Set(option)),
+            ("set", None) => Err("Argument required".to_string()),
+            ("noh" | "nohlsearch", None) => Ok(Command::NoHighlight),
+            ("bn" | "bnext", None) => Ok(Command::BufferNext),
+            ("bp" | "bprevious" | "bN" | "bNext", None) => Ok(Command::BufferPrevious),
+            ("b" | "buffer", Some(buffer)) => Ok(Command::Buffer(buffer)),
+            ("ls" | "buffers" | "files", None) => Ok(Command::ListBuffers),
+            ("bd" | "bdelete", buffer) => Ok(Command::BufferDelete {
+                buffer,
+                force: false,
+            }),
+            ("bd!" | "bdelete!", buffer) => Ok(Command::BufferDelete {
+                buffer,
+                force: true,
+            }),
+            ("sp" | "split", path) => Ok(Command::Split {
+                path,
+                vertical: false,
+            }),
+            ("vs" | "vsplit", path) => Ok(Command::Split {
+                path,
+                vertical: true,
+            }),
+            ("clo" | "close", None) => Ok(Command::Close),
+            ("on" | "only", None) => Ok(Command::Only),
+            ("tabnew" | "tabe" | "tabedit", path) => Ok(Command::TabNew(path)),
+            ("tabn" | "tabnext", None) => Ok(Command::TabNext),
+            ("tabp" | "tabprevious" | "tabN" | "tabNext", None) => Ok(Command::TabPrevious),
+            ("tabc" | "tabclose", None) => Ok(Command::TabClose),
+            _ => Err(format!("Not an editor command: {input}")),
+        }
+    }
+}
+
+impl CommandLine {
+    pub fn insert(&mut self, c: char) {
+        let idx = self.byte_idx(self.cursor);
+        self.input.insert(idx, c);
+        // a combining character merges into the grapheme before it instead of adding one
+        self.cursor = self.input[..idx + c.len_utf8()].graphemes(true).count();
+    }
+
+    /// inserts pasted text, only its first line since a command is a single line
+    pub fn insert_str(&mut self, text: &str) {
+        for c in text.lines().next().unwrap_or_default().chars() {
+            self.insert(c);
+        }
+    }
+
+    /// removes the grapheme before the cursor
+    pub fn backspace(&mut self) {
+        if self.cursor == 0 {
+            return;
+        }
+        let start = self.byte_idx(self.cursor - 1);
+        let end = self.byte_idx(self.cursor);
+        self.input.replace_range(start..end, "");
+        self.cursor -= 1;
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn move_right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.input.graphemes(true).count());
+    }
+
+    pub fn history_prev(&mut self) {
+        let idx = match self.history_idx {
+            Some(0) => return,
+            Some(idx) => idx - 1,
+            None if self.history.is_empty() => return,
+            None => self.history.len() - 1,
+        };
+        self.show_history(Some(idx));
+    }
+
+    pub fn history_next(&mut self) {
+        match self.history_idx {
+            Some(idx) if idx + 1 < self.history.len() => self.show_history(Some(idx + 1)),
+            Some(_) => self.show_history(None),
+            None => {}
+        }
+    }
+
+    fn show_history(&mut self, idx: Option<usize>) {
+        self.history_idx = idx;
+        self.input = idx.map_or_else(String::new, |idx| self.history[idx].clone());
+        self.cursor = self.input.graphemes(true).count();
+    }
+
+    /// empties the command line and returns what was typed, remembering it in the history
+    pub fn take(&mut self) -> String {
+        let input = std::mem::take(&mut self.input);
+        self.cursor = 0;
+        self.history_idx = None;
+        if !input.trim().is_empty() && self.history.last() != Some(&input) {
+            self.history.push(input.clone());
+        }
+        input
+    }
+
+    pub fn clear(&mut self) {
+        self.input.clear();
+        self.cursor = 0;
+        self.history_idx = None;
+    }
+
+    fn byte_idx(&self, col: usize) -> usize {
+        self.input
+            .grapheme_indices(true)
+            .nth(col)
+            .map_or(self.input.len(), |(idx, _)| idx)
+    }
+}
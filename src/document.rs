@@ -1,179 +1,792 @@
-use std::fs;
-use std::io::Write;
-use unicode_segmentation::UnicodeSegmentation;
+use regex::Regex;
+use ropey::{Rope, RopeSlice, iter::Chunks};
+use std::borrow::Cow;
+use std::fs::{self, OpenOptions, Permissions};
+use std::io::{Error, ErrorKind, Write};
+use std::ops::Range;
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use unicode_segmentation::{GraphemeCursor, GraphemeIncomplete, UnicodeSegmentation};
 
+use crate::file_format::FileFormat;
 use crate::highlighting::{HighlightedSegment, Highlighter};
+use crate::history::{Edit, History};
+
+const INDENT: &str = "    "; // what `>` and `<` shift by, matching what tab inserts
+const MAX_TMP_ATTEMPTS: usize = 100; // temporary file names tried before giving up on a save
+
+/// a location inside a document, `col` is counted in graphemes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Position {
+    pub fn new(line: usize, col: usize) -> Self {
+        Position { line, col }
+    }
+}
 
 pub struct RichLine {
     pub line: Vec<HighlightedSegment>,
+    highlighted: Option<usize>, // how many graphemes `line` covers, none until the line is highlighted
+    pub complete: bool, // `line` covers the whole line, long lines are only highlighted as far as they're shown
 }
 
 pub struct Document {
     pub file_name: String,
     pub extension: String,
-    pub lines: Vec<String>,        // maybe make this a richline type instead?
-    pub rich_lines: Vec<RichLine>, // cached syntax highlighting, i need to implement a more efficient way to store them but this'll do
-    pub n_lines: u16,
+    text: Rope, // rope so edits stay cheap on long lines and large files
+    pub rich_lines: Vec<RichLine>, // cached syntax highlighting, one entry per line, emptied when the line is edited
     pub highlighter: Highlighter,
+    history: History,
+    pub format: FileFormat, // encoding, line endings, final newline and BOM of the file on disk
+    saved_format: FileFormat, // what `format` was when the file was last loaded or saved
+    pub read_only: bool,
+    pub is_new: bool, // the file doesn't exist on disk until the first save
 }
 
 impl RichLine {
-    pub fn new(highlighter: &Highlighter, line: &str, extension: &str) -> Self {
-        let highlighted_segments = highlighter.highlight_line(line, extension);
+    pub fn empty() -> Self {
         RichLine {
-            line: highlighted_segments,
+            line: Vec::new(),
+            highlighted: None,
+            complete: false,
         }
     }
 
-    pub fn empty() -> Self {
-        RichLine { line: Vec::new() }
-    }
-
-    pub fn is_empty(&self) -> bool {
-        self.line.is_empty()
+    /// whether the first `graphemes` of the line are highlighted
+    pub fn covers(&self, graphemes: usize) -> bool {
+        self.complete || self.highlighted.is_some_and(|n| n >= graphemes)
     }
 
-    pub fn recalc(&mut self, highlighter: &Highlighter, line: &str, extension: &str) {
-        self.line = highlighter.highlight_line(line, extension);
+    /// highlights `text`, the first `graphemes` of the line or all of it when `complete`
+    fn recalc(
+        &mut self,
+        highlighter: &Highlighter,
+        text: &str,
+        graphemes: usize,
+        complete: bool,
+        extension: &str,
+    ) {
+        self.line = highlighter.highlight_line(text, extension);
+        self.highlighted = Some(graphemes);
+        self.complete = complete;
     }
 }
 
 impl Document {
-    pub fn new(file_name: &str) -> Self {
-        let file = fs::read_to_string(file_name).unwrap_or_else(|_| String::new());
-        let mut lines: Vec<String> = file.lines().map(str::to_string).collect();
-        if lines.is_empty() {
-            lines.push(String::new());
+    /// opens `file_name`, a file that doesn't exist yet opens as an empty document marked as new
+    pub fn new(file_name: &str) -> Result<Self, Error> {
+        if Path::new(file_name).is_dir() {
+            return Err(Error::new(ErrorKind::IsADirectory, "is a directory"));
+        }
+        let (file, is_new) = match fs::read(file_name) {
+            Ok(file) => (file, false),
+            Err(e) if e.kind() == ErrorKind::NotFound => (Vec::new(), true),
+            Err(e) => return Err(e),
+        };
+        let (mut format, mut content) = FileFormat::decode(&file);
+        if is_new {
+            format = FileFormat::default();
+        }
+        // binary files are shown for reference only, saving them back would corrupt them
+        let binary = FileFormat::looks_binary(&file);
+        if binary {
+            content = content
+                .chars()
+                .map(|c| {
+                    if c.is_control() && c != '\n' && c != '\t' {
+                        char::REPLACEMENT_CHARACTER
+                    } else {
+                        c
+                    }
+                })
+                .collect();
         }
-        let n_lines = lines.len() as u16;
+        // and so are files that lost bytes on the way in
+        let read_only = binary || FileFormat::decodes_lossily(&file);
+        let text = Rope::from_str(&content);
         let file_name = file_name.to_string();
-        // get extension if any
-        let extension = file_name
-            .rsplit_once('.')
-            .map_or_else(String::new, |(_, ext)| ext.to_string());
+        let extension = extension_of(&file_name);
 
         let highlighter = Highlighter::new();
         let mut rich_lines: Vec<RichLine> = Vec::new();
-        for _ in 0..n_lines {
+        for _ in 0..text.len_lines() {
             rich_lines.push(RichLine::empty());
         }
-        Document {
+        // pick up where the last session left off, unless the file was changed since
+        let history = History::restore(&file_name, &file).unwrap_or_default();
+        Ok(Document {
             file_name,
             extension,
-            lines,
+            text,
             rich_lines,
-            n_lines,
             highlighter,
+            history,
+            saved_format: format.clone(),
+            format,
+            read_only,
+            is_new,
+        })
+    }
+
+    /// an empty document without a file, which gets one with `:w <path>`
+    pub fn scratch() -> Self {
+        Document {
+            file_name: String::new(),
+            extension: String::new(),
+            text: Rope::new(),
+            rich_lines: vec![RichLine::empty()],
+            highlighter: Highlighter::new(),
+            history: History::default(),
+            format: FileFormat::default(),
+            saved_format: FileFormat::default(),
+            read_only: false,
+            is_new: false,
+        }
+    }
+
+    /// writes the document to a temporary file next to the original and renames it over the original,
+    /// so a failed save (disk full, permissions...) never leaves a half-written file behind
+    pub fn save(&mut self) -> Result<(), Error> {
+        if self.read_only {
+            return Err(Error::new(ErrorKind::PermissionDenied, "file is read-only"));
+        }
+        if self.file_name.is_empty() {
+            return Err(Error::new(ErrorKind::NotFound, "no file name"));
         }
+        let content = self.format.encode(&self.text.to_string())?;
+        Self::write_file(&self.file_name, &content)?;
+        self.is_new = false;
+        self.history.mark_saved();
+        // every line break was just written the same way
+        self.format.mixed_line_endings = false;
+        self.saved_format = self.format.clone();
+
+        // losing the undo history isn't worth bothering the user over, the file itself was saved
+        let _ = self.history.persist(&self.file_name, &content);
+        Ok(())
     }
 
-    pub fn save(&self) {
-        let content = self.lines.join("\n");
-        let mut file = fs::OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&self.file_name)
-            .unwrap_or_else(|e| {
-                panic!("Couldn't open or create file: {}, err: {e}", self.file_name);
-            });
-        file.write_all(content.as_bytes()).unwrap_or_else(|e| {
-            panic!("Couldn't write to file: {}, err: {e}", self.file_name);
-        });
+    /// `:w path`, writes the document to another file and keeps editing this one
+    pub fn write_copy(&self, path: &str) -> Result<(), Error> {
+        let content = self.format.encode(&self.text.to_string())?;
+        Self::write_file(path, &content)
     }
 
-    pub fn insert_char(&mut self, c: char, line: u16, col: u16) {
-        if line as usize >= self.lines.len() {
+    fn write_file(file_name: &str, content: &[u8]) -> Result<(), Error> {
+        // write through symlinks instead of replacing them
+        let target = fs::canonicalize(file_name).unwrap_or_else(|_| PathBuf::from(file_name));
+        let dir = match target.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let name = target.file_name().map_or_else(
+            || file_name.to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        let permissions = fs::metadata(&target).ok().map(|meta| meta.permissions());
+        let (tmp, file) = Self::create_tmp(dir, &name, permissions.as_ref())?;
+
+        let result = Self::write_atomically(file, &tmp, &target, content, permissions);
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        result
+    }
+
+    /// creates the temporary file under a name no other save uses, failing instead of reusing a file that's already
+    /// there so a file or symlink planted under that name is never written through
+    fn create_tmp(
+        dir: &Path,
+        name: &str,
+        permissions: Option<&Permissions>,
+    ) -> Result<(PathBuf, fs::File), Error> {
+        static SAVES: AtomicUsize = AtomicUsize::new(0);
+        for _ in 0..MAX_TMP_ATTEMPTS {
+            let save = SAVES.fetch_add(1, Ordering::Relaxed);
+            let tmp = dir.join(format!(".{name}.pascal-tmp.{}.{save}", process::id()));
+            let mut options = OpenOptions::new();
+            options.write(true).create_new(true);
+            // the copy gets the file's mode from the start, so its contents are never readable by more people
+            #[cfg(unix)]
+            if let Some(permissions) = permissions {
+                options.mode(permissions.mode());
+            }
+            match options.open(&tmp) {
+                Ok(file) => return Ok((tmp, file)),
+                // left behind by a crashed editor with the same pid, try the next name
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Err(Error::new(
+            ErrorKind::AlreadyExists,
+            "couldn't create a temporary file",
+        ))
+    }
+
+    fn write_atomically(
+        mut file: fs::File,
+        tmp: &Path,
+        target: &Path,
+        content: &[u8],
+        permissions: Option<Permissions>,
+    ) -> Result<(), Error> {
+        // the umask may have taken bits off the mode
+        if let Some(permissions) = permissions {
+            file.set_permissions(permissions)?;
+        }
+        file.write_all(content)?;
+        file.sync_all()?;
+        fs::rename(tmp, target)
+    }
+
+    /// points the document at another file, the next save writes there
+    pub fn set_file_name(&mut self, file_name: &str) {
+        self.file_name = file_name.to_string();
+        self.extension = extension_of(file_name);
+        // highlighting depends on the extension
+        for rich_line in &mut self.rich_lines {
+            *rich_line = RichLine::empty();
+        }
+    }
+
+    pub fn n_lines(&self) -> usize {
+        self.text.len_lines()
+    }
+
+    /// returns the contents of a line without its line break
+    pub fn line(&self, line: usize) -> String {
+        self.line_slice(line).to_string()
+    }
+
+    /// the graphemes of a line, read from the rope without copying the line
+    pub fn graphemes(&self, line: usize) -> impl Iterator<Item = Cow<'_, str>> {
+        RopeGraphemes::new(self.line_slice(line)).map(Cow::from)
+    }
+
+    /// length of a line in graphemes, which is the unit the cursor moves in
+    pub fn line_len(&self, line: usize) -> usize {
+        grapheme_count(self.line_slice(line))
+    }
+
+    /// a line without its line break, borrowed from the rope
+    fn line_slice(&self, line: usize) -> RopeSlice<'_> {
+        let slice = self.text.line(line);
+        let len = slice.len_chars();
+        if len > 0 && slice.char(len - 1) == '\n' {
+            slice.slice(..len - 1)
+        } else {
+            slice
+        }
+    }
+
+    /// converts a grapheme position into an absolute char index into the rope, a column past the end of the line
+    /// gives the end of the line
+    fn char_idx(&self, line: usize, col: usize) -> usize {
+        let (chars, _) = self.col_to_chars(line, col);
+        self.text.line_to_char(line) + chars
+    }
+
+    /// like `char_idx`, but none when the line is shorter than `col`
+    fn checked_char_idx(&self, line: usize, col: usize) -> Option<usize> {
+        let (chars, cols) = self.col_to_chars(line, col);
+        (cols == col).then(|| self.text.line_to_char(line) + chars)
+    }
+
+    /// the chars taken up by the first `col` graphemes of a line, and how many graphemes there were up to `col`
+    fn col_to_chars(&self, line: usize, col: usize) -> (usize, usize) {
+        let slice = self.line_slice(line);
+        if is_ascii(slice) {
+            let cols = col.min(slice.len_chars());
+            return (cols, cols);
+        }
+        RopeGraphemes::new(slice)
+            .take(col)
+            .fold((0, 0), |(chars, cols), g| (chars + g.len_chars(), cols + 1))
+    }
+
+    /// converts an absolute char index back into a grapheme position
+    fn position_of(&self, char_idx: usize) -> Position {
+        let line = self.text.char_to_line(char_idx);
+        let prefix = self.text.slice(self.text.line_to_char(line)..char_idx);
+        Position::new(line, grapheme_count(prefix))
+    }
+
+    /// makes sure the syntax highlighting of a line covers its first `graphemes`
+    /// a long line is highlighted only a bit further than that, so editing it doesn't highlight all of it every time
+    pub fn highlight(&mut self, line: usize, graphemes: usize) {
+        if line >= self.n_lines() || self.rich_lines[line].covers(graphemes) {
             return;
         }
-        let line_str = &mut self.lines[line as usize];
-        let mut graphemes: Vec<&str> =
-            UnicodeSegmentation::graphemes(line_str.as_str(), true).collect();
-        if col as usize > graphemes.len() {
+        // a margin so scrolling sideways doesn't highlight again at every step
+        let graphemes = graphemes.saturating_mul(2).max(1000);
+        let (chars, cols) = self.col_to_chars(line, graphemes);
+        let text = self.line_slice(line).slice(..chars).to_string();
+        let complete = chars == self.line_slice(line).len_chars();
+        self.rich_lines[line].recalc(&self.highlighter, &text, cols, complete, &self.extension);
+    }
+
+    /// every edit goes through here and `remove_text` so it ends up in the undo history
+    fn insert_text(&mut self, char_idx: usize, text: &str) {
+        if self.read_only {
             return;
         }
-        let binding = c.to_string();
-        graphemes.insert(col as usize, &binding);
-        *line_str = graphemes.concat();
-        self.rich_lines[line as usize].recalc(&self.highlighter, line_str, &self.extension);
+        let edit = Edit::Insert {
+            at: char_idx,
+            text: text.to_string(),
+        };
+        self.apply(&edit);
+        self.history.record(edit, self.position_of(char_idx));
     }
 
-    pub fn remove_char(&mut self, line: u16, col: u16) {
-        if line as usize >= self.lines.len() {
+    fn remove_text(&mut self, range: Range<usize>) {
+        if self.read_only {
             return;
         }
-        let line_str = &mut self.lines[line as usize];
-        let mut graphemes: Vec<&str> =
-            UnicodeSegmentation::graphemes(line_str.as_str(), true).collect();
-        if col as usize >= graphemes.len() {
+        let edit = Edit::Remove {
+            at: range.start,
+            text: self.text.slice(range.clone()).to_string(),
+        };
+        let cursor = self.position_of(range.start);
+        self.apply(&edit);
+        self.history.record(edit, cursor);
+    }
+
+    /// changes the rope and keeps the highlighting cache aligned with it, without touching the history
+    fn apply(&mut self, edit: &Edit) {
+        match edit {
+            Edit::Insert { at, text } => {
+                let line = self.text.char_to_line(*at);
+                self.text.insert(*at, text);
+                let added_lines = text.matches('\n').count();
+                self.rich_lines[line] = RichLine::empty();
+                // one splice instead of an insert per line keeps pasting long texts fast
+                if added_lines > 0 {
+                    let below = line + 1;
+                    self.rich_lines.splice(
+                        below..below,
+                        std::iter::repeat_with(RichLine::empty).take(added_lines),
+                    );
+                }
+            }
+            Edit::Remove { at, text } => {
+                let line = self.text.char_to_line(*at);
+                let removed_lines = text.matches('\n').count();
+                self.text.remove(*at..*at + text.chars().count());
+                self.rich_lines[line] = RichLine::empty();
+                if removed_lines > 0 {
+                    self.rich_lines.drain(line + 1..=line + removed_lines);
+                }
+            }
+        }
+    }
+
+    /// groups every following edit into a single undo step until `end_undo_group` is called
+    pub fn begin_undo_group(&mut self, cursor: Position) {
+        self.history.begin_group(cursor);
+    }
+
+    pub fn end_undo_group(&mut self, cursor: Position) {
+        self.history.end_group(cursor);
+    }
+
+    /// whether the text and its format are back to what was last saved, or loaded if it wasn't saved yet
+    pub fn is_saved(&self) -> bool {
+        self.history.is_saved() && self.format == self.saved_format
+    }
+
+    /// reverts the last undo step, returns where the cursor was before it was made
+    pub fn undo(&mut self) -> Option<Position> {
+        if self.read_only {
+            return None;
+        }
+        let group = self.history.pop_undo()?;
+        for edit in group.edits.iter().rev() {
+            self.apply(&edit.inverse());
+        }
+        let cursor = group.cursor_before;
+        self.history.push_redo(group);
+        Some(cursor)
+    }
+
+    /// re-applies the last undone step, returns where the cursor was after it was made
+    pub fn redo(&mut self) -> Option<Position> {
+        if self.read_only {
+            return None;
+        }
+        let group = self.history.pop_redo()?;
+        for edit in &group.edits {
+            self.apply(edit);
+        }
+        let cursor = group.cursor_after;
+        self.history.push_undo(group);
+        Some(cursor)
+    }
+
+    pub fn insert_char(&mut self, c: char, line: usize, col: usize) {
+        if line >= self.n_lines() {
             return;
         }
-        graphemes.remove(col as usize);
-        *line_str = graphemes.concat();
-        self.rich_lines[line as usize].recalc(&self.highlighter, line_str, &self.extension);
+        if let Some(idx) = self.checked_char_idx(line, col) {
+            self.insert_text(idx, c.encode_utf8(&mut [0; 4]));
+        }
     }
 
-    pub fn join_lines(&mut self, line: u16) {
-        if line == 0 || line as usize >= self.lines.len() {
+    pub fn remove_char(&mut self, line: usize, col: usize) {
+        if line >= self.n_lines() {
             return;
         }
+        if let Some(end) = self.checked_char_idx(line, col + 1) {
+            let start = self.char_idx(line, col);
+            self.remove_text(start..end);
+        }
+    }
 
-        let current_line = self.lines.remove(line as usize);
-        let prev_line_idx = line - 1;
-        self.lines[prev_line_idx as usize].push_str(&current_line);
-        self.n_lines -= 1;
-        self.rich_lines.remove(line as usize);
-        self.rich_lines[prev_line_idx as usize].recalc(
-            &self.highlighter,
-            &self.lines[prev_line_idx as usize],
-            &self.extension,
-        );
+    pub fn join_lines(&mut self, line: usize) {
+        if line == 0 || line >= self.n_lines() {
+            return;
+        }
+        // removing the line break at the end of the previous line merges the two
+        let line_start = self.text.line_to_char(line);
+        self.remove_text(line_start - 1..line_start);
     }
 
-    pub fn newline(&mut self, line: u16, col: u16) {
-        let line_str = self.lines[line as usize].clone();
-        let mut graphemes: Vec<&str> =
-            UnicodeSegmentation::graphemes(line_str.as_str(), true).collect();
-        // case 1: user tries to add a new line at the end of the current line
-        if col as usize == graphemes.len() {
-            self.lines.insert(line as usize + 1, String::new());
-            self.rich_lines.insert(
-                line as usize + 1,
-                RichLine::new(&Highlighter::new(), "", &self.extension),
+    pub fn newline(&mut self, line: usize, col: usize) {
+        if line >= self.n_lines() {
+            return;
+        }
+        let idx = self.char_idx(line, col);
+        self.insert_text(idx, "\n");
+    }
+
+    /// the nearest position that exists in the document
+    pub fn clamp(&self, pos: Position) -> Position {
+        let line = pos.line.min(self.n_lines() - 1);
+        Position::new(line, pos.col.min(self.line_len(line)))
+    }
+
+    /// converts a position into a char index, clamping it to the document
+    fn clamped_char_idx(&self, pos: Position) -> usize {
+        let pos = self.clamp(pos);
+        self.char_idx(pos.line, pos.col)
+    }
+
+    /// the text from `start` up to but not including `end`, line breaks included
+    pub fn text_range(&self, start: Position, end: Position) -> String {
+        let start = self.clamped_char_idx(start);
+        let end = self.clamped_char_idx(end).max(start);
+        self.text.slice(start..end).to_string()
+    }
+
+    /// removes the text from `start` up to but not including `end`
+    pub fn delete_range(&mut self, start: Position, end: Position) {
+        let start = self.clamped_char_idx(start);
+        let end = self.clamped_char_idx(end);
+        if start < end {
+            self.remove_text(start..end);
+        }
+    }
+
+    /// inserts `text` (which may span several lines) at `pos`, returns the position right after it
+    pub fn insert_str(&mut self, pos: Position, text: &str) -> Position {
+        let idx = self.clamped_char_idx(pos);
+        if text.is_empty() || self.read_only {
+            return self.position_of(idx);
+        }
+        self.insert_text(idx, text);
+        self.position_of(idx + text.chars().count())
+    }
+
+    /// removes whole lines along with their line breaks, a document always keeps at least one line
+    pub fn delete_lines(&mut self, first: usize, last: usize) {
+        let last = last.min(self.n_lines() - 1);
+        if last + 1 < self.n_lines() {
+            self.delete_range(Position::new(first, 0), Position::new(last + 1, 0));
+        } else if first > 0 {
+            // the last line has no line break of its own, take the one before it instead
+            let prev_len = self.line_len(first - 1);
+            self.delete_range(
+                Position::new(first - 1, prev_len),
+                Position::new(last, self.line_len(last)),
+            );
+        } else {
+            self.delete_range(
+                Position::new(0, 0),
+                Position::new(last, self.line_len(last)),
             );
-            self.n_lines += 1;
+        }
+    }
+
+    /// shifts a line right by one indent, empty lines are left alone like in vim
+    pub fn indent(&mut self, line: usize) {
+        if line < self.n_lines() && self.line_len(line) > 0 {
+            self.insert_str(Position::new(line, 0), INDENT);
+        }
+    }
+
+    /// shifts a line left by up to one indent, a leading tab counts as a whole indent
+    pub fn dedent(&mut self, line: usize) {
+        if line >= self.n_lines() {
             return;
         }
-        // case 2: user tries to either newline at the start of in the middle of a line
-        let new_line = graphemes.split_off(col as usize);
-        self.lines[line as usize] = graphemes.concat();
-        self.lines.insert(line as usize + 1, new_line.concat());
-        self.n_lines += 1;
+        let line_str = self.line(line);
+        let width = if line_str.starts_with('\t') {
+            1
+        } else {
+            line_str
+                .chars()
+                .take(INDENT.len())
+                .take_while(|&c| c == ' ')
+                .count()
+        };
+        self.delete_range(Position::new(line, 0), Position::new(line, width));
+    }
 
-        self.rich_lines[line as usize].recalc(
-            &self.highlighter,
-            &self.lines[line as usize],
-            &self.extension,
-        );
-        self.rich_lines.insert(
-            line as usize + 1,
-            RichLine::new(&Highlighter::new(), &new_line.concat(), &self.extension),
-        );
+    /// `iw` and `aw`: the run of word characters, punctuation or whitespace under `pos` on its line,
+    /// `aw` adds the whitespace after it, or before it when there is none after
+    /// returns the start and the end, which isn't included
+    pub fn word_object(&self, pos: Position, around: bool) -> (Position, Position) {
+        let line_str = self.line(pos.line);
+        let classes: Vec<CharClass> = line_str.graphemes(true).map(CharClass::of).collect();
+        let len = classes.len();
+        if len == 0 {
+            return (Position::new(pos.line, 0), Position::new(pos.line, 0));
+        }
+        let run_start = |mut col: usize| {
+            while col > 0 && classes[col - 1] == classes[col] {
+                col -= 1;
+            }
+            col
+        };
+        let run_end = |mut col: usize| {
+            let class = classes[col];
+            while col < len && classes[col] == class {
+                col += 1;
+            }
+            col
+        };
+        let col = pos.col.min(len - 1);
+        let mut start = run_start(col);
+        let mut end = run_end(col);
+        if around {
+            if classes[col] == CharClass::Space {
+                if end < len {
+                    end = run_end(end);
+                }
+            } else if end < len && classes[end] == CharClass::Space {
+                end = run_end(end);
+            } else if start > 0 && classes[start - 1] == CharClass::Space {
+                start = run_start(start - 1);
+            }
+        }
+        (Position::new(pos.line, start), Position::new(pos.line, end))
+    }
+
+    /// `i"` and `a"`: the quoted string around `pos` on its line, or the next one after it
+    /// quotes escaped with a backslash don't count, `a"` takes the quotes and the whitespace after them
+    pub fn quote_object(
+        &self,
+        pos: Position,
+        quote: char,
+        around: bool,
+    ) -> Option<(Position, Position)> {
+        let line_str = self.line(pos.line);
+        let graphemes: Vec<&str> = line_str.graphemes(true).collect();
+        let mut quotes = Vec::new();
+        let mut escaped = false;
+        for (col, grapheme) in graphemes.iter().enumerate() {
+            if escaped {
+                escaped = false;
+            } else if *grapheme == "\\" {
+                escaped = true;
+            } else if grapheme.chars().eq([quote]) {
+                quotes.push(col);
+            }
+        }
+        // quotes pair up from the start of the line like in vim
+        let (open, close) = quotes
+            .chunks_exact(2)
+            .map(|pair| (pair[0], pair[1]))
+            .find(|&(_, close)| pos.col <= close)?;
+        if !around {
+            return Some((
+                Position::new(pos.line, open + 1),
+                Position::new(pos.line, close),
+            ));
+        }
+        let is_space = |col: usize| CharClass::of(graphemes[col]) == CharClass::Space;
+        let (mut start, mut end) = (open, close + 1);
+        if end < graphemes.len() && is_space(end) {
+            while end < graphemes.len() && is_space(end) {
+                end += 1;
+            }
+        } else {
+            while start > 0 && is_space(start - 1) {
+                start -= 1;
+            }
+        }
+        Some((Position::new(pos.line, start), Position::new(pos.line, end)))
+    }
+
+    /// `i(` and `a(` and the other bracket pairs: the innermost pair around `pos`, which may span several lines
+    /// brackets on lines of their own leave their line breaks and indentation out of `i(` like in vim
+    pub fn bracket_object(
+        &self,
+        pos: Position,
+        open: char,
+        close: char,
+        around: bool,
+    ) -> Option<(Position, Position)> {
+        let len = self.text.len_chars();
+        let idx = self.clamped_char_idx(pos);
+        let open_idx = if idx < len && self.text.char(idx) == open {
+            idx
+        } else {
+            // the cursor may sit on the closing bracket, so only what comes before it is searched
+            let mut depth = 0;
+            let mut chars = self.text.chars_at(idx);
+            let mut i = idx;
+            loop {
+                let c = chars.prev()?;
+                i -= 1;
+                if c == close {
+                    depth += 1;
+                } else if c == open {
+                    if depth == 0 {
+                        break i;
+                    }
+                    depth -= 1;
+                }
+            }
+        };
+        let mut depth = 0;
+        let mut close_idx = None;
+        for (i, c) in self.text.chars_at(open_idx + 1).enumerate() {
+            if c == open {
+                depth += 1;
+            } else if c == close {
+                if depth == 0 {
+                    close_idx = Some(open_idx + 1 + i);
+                    break;
+                }
+                depth -= 1;
+            }
+        }
+        let close_idx = close_idx?;
+        if around {
+            return Some((self.position_of(open_idx), self.position_of(close_idx + 1)));
+        }
+        let mut start = open_idx + 1;
+        let mut end = close_idx;
+        if start < end && self.text.char(start) == '\n' {
+            start += 1;
+        }
+        let close_line_start = self.text.line_to_char(self.text.char_to_line(close_idx));
+        if close_line_start >= start
+            && self
+                .text
+                .slice(close_line_start..close_idx)
+                .chars()
+                .all(char::is_whitespace)
+        {
+            end = close_line_start;
+        }
+        Some((self.position_of(start.min(end)), self.position_of(end)))
+    }
+
+    /// `it` and `at`: the innermost pair of matching html/xml tags around `pos`, self-closing tags are skipped
+    pub fn tag_object(&self, pos: Position, around: bool) -> Option<(Position, Position)> {
+        let text = self.text.to_string();
+        let cursor = self.text.char_to_byte(self.clamped_char_idx(pos));
+        let tag = Regex::new(r"<(/?)([A-Za-z][\w:.-]*)[^>]*?(/?)>").ok()?;
+        let mut open_tags: Vec<(&str, Range<usize>)> = Vec::new();
+        let mut found = None;
+        for captures in tag.captures_iter(&text) {
+            let whole = captures.get(0)?.range();
+            let name = captures.get(2)?.as_str();
+            if !captures[3].is_empty() {
+                continue;
+            }
+            if captures[1].is_empty() {
+                open_tags.push((name, whole));
+                continue;
+            }
+            // a closing tag closes the nearest open one with its name, tags opened after that were never closed
+            let Some(i) = open_tags.iter().rposition(|(open, _)| *open == name) else {
+                continue;
+            };
+            let opening = open_tags[i].1.clone();
+            open_tags.truncate(i);
+            // inner pairs close first, so the first pair around the cursor is the innermost one
+            if opening.start <= cursor && cursor < whole.end {
+                found = Some((opening, whole));
+                break;
+            }
+        }
+        let (opening, closing) = found?;
+        let (start, end) = if around {
+            (opening.start, closing.end)
+        } else {
+            (opening.end, closing.start)
+        };
+        Some((
+            self.position_of(self.text.byte_to_char(start)),
+            self.position_of(self.text.byte_to_char(end)),
+        ))
+    }
+
+    /// `ip` and `ap`: the lines of the paragraph (or run of blank lines) around `line`,
+    /// `ap` adds the blank lines after it, or before it when there are none after
+    pub fn paragraph_object(&self, line: usize, around: bool) -> (usize, usize) {
+        let last_line = self.n_lines() - 1;
+        let blank = |line: usize| self.line(line).trim().is_empty();
+        let run_start = |mut line: usize| {
+            let kind = blank(line);
+            while line > 0 && blank(line - 1) == kind {
+                line -= 1;
+            }
+            line
+        };
+        let run_end = |mut line: usize| {
+            let kind = blank(line);
+            while line < last_line && blank(line + 1) == kind {
+                line += 1;
+            }
+            line
+        };
+        let line = line.min(last_line);
+        let (mut first, mut last) = (run_start(line), run_end(line));
+        if around {
+            if last < last_line {
+                last = run_end(last + 1);
+            } else if first > 0 {
+                first = run_start(first - 1);
+            }
+        }
+        (first, last)
     }
 
     /// finds the next word operating on the following boundaries: whitespace, punctuation, any non-word character or underscore
-    pub fn next_word(&self, line: u16, col: u16) -> (u16, u16) {
-        let line_str = self.lines[line as usize].clone();
+    pub fn next_word(&self, pos: Position) -> Position {
+        let line = pos.line;
+        let line_str = self.line(line);
         let graphemes: Vec<&str> =
             UnicodeSegmentation::graphemes(line_str.as_str(), true).collect();
-        let mut cur_col = col as usize;
+        let mut cur_col = pos.col;
         let len = graphemes.len();
         // are we on the last character of the line? if so move to the next one if there is one
         if cur_col >= len {
-            if line + 1 < self.n_lines {
-                return (line + 1, 0);
+            if line + 1 < self.n_lines() {
+                return Position::new(line + 1, 0);
             }
-            return (line, len as u16);
+            return Position::new(line, len);
         }
         let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
         // find the next non-word character aka exit our current word
@@ -197,24 +810,34 @@ impl Document {
             cur_col += 1;
         }
 
-        (line, cur_col as u16)
+        Position::new(line, cur_col)
     }
 
-    pub fn prev_word(&self, line: u16, col: u16) -> (u16, u16) {
-        let line_str = self.lines[line as usize].clone();
+    /// the position just past the end of the word under `pos`, or `pos` itself when it isn't on a word
+    pub fn word_end(&self, pos: Position) -> Position {
+        let line_str = self.line(pos.line);
+        let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+        let word_len = line_str
+            .graphemes(true)
+            .skip(pos.col)
+            .take_while(|g| is_word_char(g.chars().next().unwrap_or(' ')))
+            .count();
+        Position::new(pos.line, pos.col + word_len)
+    }
+
+    pub fn prev_word(&self, pos: Position) -> Position {
+        let line = pos.line;
+        let line_str = self.line(line);
         let graphemes: Vec<&str> =
             UnicodeSegmentation::graphemes(line_str.as_str(), true).collect();
-        let mut cur_col = col as usize;
+        let mut cur_col = pos.col;
 
         // are we on the first character of the current line? if so move to the previous line if it exists
         if cur_col == 0 {
             if line > 0 {
-                let prev_line_str = self.lines[(line - 1) as usize].clone();
-                let prev_graphemes: Vec<&str> =
-                    UnicodeSegmentation::graphemes(prev_line_str.as_str(), true).collect();
-                return (line - 1, prev_graphemes.len() as u16);
+                return Position::new(line - 1, self.line_len(line - 1));
             }
-            return (line, 0);
+            return Position::new(line, 0);
         }
 
         let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
@@ -237,6 +860,100 @@ impl Document {
             cur_col -= 1;
         }
 
-        (line, cur_col as u16)
+        Position::new(line, cur_col)
+    }
+}
+
+/// what `iw` and `aw` tell apart, a word is a run of graphemes of the same class
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Space,
+    Word,
+    Punctuation,
+}
+
+impl CharClass {
+    fn of(grapheme: &str) -> Self {
+        match grapheme.chars().next() {
+            Some(c) if c.is_whitespace() => CharClass::Space,
+            Some(c) if c.is_alphanumeric() || c == '_' => CharClass::Word,
+            _ => CharClass::Punctuation,
+        }
+    }
+}
+
+// get extension if any
+fn extension_of(file_name: &str) -> String {
+    file_name
+        .rsplit_once('.')
+        .map_or_else(String::new, |(_, ext)| ext.to_string())
+}
+
+/// in ascii text every char is a grapheme of its own, the only pair forming one is a line break
+fn is_ascii(slice: RopeSlice) -> bool {
+    slice.len_bytes() == slice.len_chars()
+}
+
+fn grapheme_count(slice: RopeSlice) -> usize {
+    if is_ascii(slice) {
+        slice.len_chars()
+    } else {
+        RopeGraphemes::new(slice).count()
+    }
+}
+
+/// the graphemes of a piece of the rope, found chunk by chunk so the text isn't copied into a string first
+struct RopeGraphemes<'a> {
+    text: RopeSlice<'a>,
+    chunks: Chunks<'a>,
+    chunk: &'a str,
+    chunk_start: usize, // byte index of `chunk` in `text`
+    cursor: GraphemeCursor,
+}
+
+impl<'a> RopeGraphemes<'a> {
+    fn new(text: RopeSlice<'a>) -> Self {
+        let mut chunks = text.chunks();
+        let chunk = chunks.next().unwrap_or("");
+        RopeGraphemes {
+            text,
+            chunks,
+            chunk,
+            chunk_start: 0,
+            cursor: GraphemeCursor::new(0, text.len_bytes(), true),
+        }
+    }
+}
+
+impl<'a> Iterator for RopeGraphemes<'a> {
+    type Item = RopeSlice<'a>;
+
+    fn next(&mut self) -> Option<RopeSlice<'a>> {
+        let start = self.cursor.cur_cursor();
+        let end = loop {
+            match self.cursor.next_boundary(self.chunk, self.chunk_start) {
+                Ok(Some(end)) => break end,
+                Err(GraphemeIncomplete::NextChunk) => {
+                    self.chunk_start += self.chunk.len();
+                    self.chunk = self.chunks.next().unwrap_or("");
+                }
+                Err(GraphemeIncomplete::PreContext(idx)) => {
+                    let (chunk, chunk_start, _, _) = self.text.chunk_at_byte(idx.saturating_sub(1));
+                    self.cursor.provide_context(chunk, chunk_start);
+                }
+                // the end of the text, the other errors only come up when going backwards
+                Ok(None) | Err(_) => return None,
+            }
+        };
+        // a grapheme straddling two chunks is sliced out of the rope, the others out of the chunk
+        if start < self.chunk_start {
+            Some(
+                self.text
+                    .slice(self.text.byte_to_char(start)..self.text.byte_to_char(end)),
+            )
+        } else {
+            let chunk_text = &self.chunk[start - self.chunk_start..end - self.chunk_start];
+            Some(chunk_text.into())
+        }
     }
 }
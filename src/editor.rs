@@ -1,38 +1,97 @@
+use crate::buffer::Buffer;
+use crate::cli::Args;
+use crate::command::CommandLine;
+use crate::motion::Motion;
+use crate::operator::PendingKeys;
+use crate::register::Registers;
 use crate::render::Renderer;
+use crate::screen::{Screen, display_width};
+use crate::search::Search;
 use crate::statusbar::StatusBar;
+use crate::substitute::Substitution;
 use crate::term::Terminal;
-use crate::{document::Document, mode::Mode};
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, read};
-use std::{io::Error, path::Path};
-use unicode_segmentation::UnicodeSegmentation;
-use unicode_width::UnicodeWidthChar;
+use crate::window::Windows;
+use crate::{
+    document::{Document, Position},
+    mode::Mode,
+};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers, MouseEventKind, read};
+use std::{io::Error, mem, path::Path};
+mod buffers;
+mod commands;
+mod mouse;
+mod operator;
+mod registers;
+mod search;
+mod substitute;
+mod tabs;
+mod visual;
+mod windows;
+mod wrap;
 const NAME: &str = "pascal-editor";
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 pub struct Editor {
     pub term: Terminal,
     quit: bool,
-    mode: Mode,
+    pub mode: Mode,
     pub docu: Document,
-    pub cursor_x: u16,
-    pub cursor_y: u16,
-    pub top_offset: u16,
-    pub left_offset: u16,
+    pub cursor_x: usize, // document coordinates, in graphemes
+    pub cursor_y: usize,
+    pub top_offset: usize,
+    pub left_offset: usize, // the grapheme lines are shown from, with wrapping only the top line's row it starts on
     pub status_bar: StatusBar,
+    pub command_line: CommandLine,
+    pub search_line: CommandLine, // the search prompt, kept apart so searches and commands have their own history
+    pub search: Search,
+    pub substitution: Option<Substitution>, // a `:s///c` waiting for matches to be confirmed
+    pub visual_anchor: Position, // where the selection started, the cursor is the other end
+    pub registers: Registers,
+    pending: PendingKeys, // a count or operator waiting for the rest of the command
+    buffers: Vec<Buffer>, // every other open file, the current one lives in `docu` and the fields above
+    pub buffer_number: usize, // the buffer in `docu`
+    next_buffer_number: usize,
+    pub windows: Windows,
+    tabs: Vec<Windows>, // the window layouts of the other tab pages, `windows` goes between them at `tab`
+    tab: usize,
+    screen: Screen, // what the terminal shows, so a render only has to send what changed
+    pub wrap: bool, // `:set wrap`, long lines continue on the rows below instead of scrolling sideways
 }
 
 impl Editor {
-    pub fn build(file_path: &str) -> Result<Editor, Error> {
-        let docu = Document::new(file_path);
+    /// opens every file on the command line, the first one is shown and the others wait as buffers
+    pub fn build(args: &Args) -> Result<Editor, Error> {
+        let mut docus = Vec::new();
+        for file in &args.files {
+            let mut docu = Document::new(&file.path).map_err(|e| {
+                Error::new(
+                    e.kind(),
+                    format!("Could not open file '{}': {e}", file.path),
+                )
+            })?;
+            docu.read_only |= args.read_only;
+            docus.push((docu, file.position));
+        }
+        let (docu, position) = if docus.is_empty() {
+            (Document::scratch(), None)
+        } else {
+            docus.remove(0)
+        };
         let term = Terminal::build()?;
         Terminal::noblink_cursor()?;
-        let file_name = Path::new(file_path)
-            .file_name()
-            .and_then(|name| name.to_str())
-            .unwrap_or(file_path)
-            .to_string();
-        let status_bar = StatusBar::new(file_name, Mode::Normal, false);
-        Ok(Editor {
+        let mut status_bar = StatusBar::new(display_name(&docu.file_name), Mode::Normal, false);
+        status_bar.read_only = docu.read_only;
+        status_bar.is_new = docu.is_new;
+        let buffers: Vec<Buffer> = docus
+            .into_iter()
+            .zip(2..)
+            .map(|((docu, position), number)| {
+                let mut buffer = Buffer::new(number, docu);
+                buffer.cursor = buffer.docu.clamp(position.unwrap_or_default());
+                buffer
+            })
+            .collect();
+        let mut editor = Editor {
             term,
             quit: false,
             mode: Mode::Normal,
@@ -42,7 +101,28 @@ impl Editor {
             top_offset: 0,
             left_offset: 0,
             status_bar,
-        })
+            command_line: CommandLine::default(),
+            search_line: CommandLine::default(),
+            search: Search::default(),
+            substitution: None,
+            visual_anchor: Position::default(),
+            registers: Registers::default(),
+            pending: PendingKeys::default(),
+            next_buffer_number: buffers.len() + 2,
+            buffers,
+            buffer_number: 1,
+            windows: Windows::new(1),
+            tabs: Vec::new(),
+            tab: 0,
+            screen: Screen::default(),
+            wrap: false,
+        };
+        editor.arrange_windows();
+        if let Some(position) = position {
+            editor.set_cursor(editor.docu.clamp(position));
+            editor.update_offsets();
+        }
+        Ok(editor)
     }
 
     pub fn run(&mut self) {
@@ -68,8 +148,12 @@ impl Editor {
     }
 
     fn render(&mut self) -> Result<(), Error> {
-        Renderer::new(self).render()?;
-        Ok(())
+        self.highlight_visible_lines();
+        // the renderer reads the editor while drawing into the screen, so the screen is lent out meanwhile
+        let mut screen = mem::take(&mut self.screen);
+        let result = Renderer::new(self, &mut screen).render();
+        self.screen = screen;
+        result
     }
 
     fn welcome(&self) -> Result<(), Error> {
@@ -87,6 +171,7 @@ impl Editor {
 
         // TODO: HANDLE BASED ON MODE
         if let Some(key) = event.as_key_press_event() {
+            self.status_bar.message = None;
             match self.mode {
                 Mode::Normal => {
                     self.handle_normal_mode_key_event(key);
@@ -94,65 +179,97 @@ impl Editor {
                 Mode::Insert => {
                     self.handle_insert_mode_key_event(key);
                 }
+                Mode::Command => {
+                    self.handle_command_mode_key_event(key);
+                }
+                Mode::Search => {
+                    self.handle_search_mode_key_event(key);
+                }
+                Mode::Confirm => {
+                    self.handle_confirm_mode_key_event(key);
+                }
+                Mode::Visual | Mode::VisualLine | Mode::VisualBlock => {
+                    self.handle_visual_mode_key_event(key);
+                }
             }
             self.render()?;
         }
+        // every move of the pointer is reported, none of them change anything
+        if let Event::Mouse(mouse) = event
+            && mouse.kind != MouseEventKind::Moved
+        {
+            self.handle_mouse_event(mouse);
+            self.render()?;
+        }
+        if let Event::Paste(text) = &event {
+            self.status_bar.message = None;
+            self.paste(text);
+            self.render()?;
+        }
         // handle resize events
         if let Some(size) = event.as_resize_event() {
             let (width, height) = size;
             self.term.width = width;
             self.term.height = height;
-            self.update_offsets();
-            Terminal::clear()?;
+            self.arrange_windows();
             self.render()?;
         }
         Ok(())
     }
 
     fn handle_normal_mode_key_event(&mut self, key: KeyEvent) {
+        if self.handle_pending_keys(key) {
+            return;
+        }
         match (key.code, key.modifiers) {
             (KeyCode::Char('q'), KeyModifiers::CONTROL) => {
                 self.quit = true;
             }
             (KeyCode::Char('s'), KeyModifiers::CONTROL) => {
-                self.docu.save();
-                self.status_bar.has_unsaved_changes = false;
+                self.save();
             }
-            (
-                KeyCode::Char('h' | 'j' | 'k' | 'l' | 'w' | 'b')
-                | KeyCode::Left
-                | KeyCode::Right
-                | KeyCode::Down
-                | KeyCode::Up,
-                KeyModifiers::NONE,
-            ) => {
-                self.handle_movement(key.code);
+            (KeyCode::Char('e'), KeyModifiers::CONTROL) => {
+                // convert between LF and CRLF, takes effect on the next save
+                let format = &mut self.docu.format;
+                format.line_ending = format.line_ending.toggled();
+                let line_ending = format.line_ending;
+                self.status_bar.has_unsaved_changes = !self.docu.is_saved();
+                self.status_bar.message = Some(format!("line endings set to {line_ending}"));
             }
             (KeyCode::Char('i'), KeyModifiers::NONE) => {
-                self.enter_insert();
-            }
-            (KeyCode::Char('t'), KeyModifiers::NONE) => {
-                // move cursor to the bottom of the document
-                if self.docu.n_lines > 0 {
-                    self.cursor_y = self.docu.n_lines - 1;
-
-                    // Set cursor to end of last line
-                    if let Some(line) = self.docu.lines.get(self.cursor_y as usize) {
-                        self.cursor_x = line.graphemes(true).count() as u16;
-                    } else {
-                        self.cursor_x = 0;
-                    }
+                if self.docu.read_only {
+                    self.status_bar.message = Some("file is read-only".to_string());
                 } else {
-                    self.cursor_y = 0;
-                    self.cursor_x = 0;
+                    self.enter_insert();
                 }
-                self.update_offsets();
             }
-            (KeyCode::Char('g'), KeyModifiers::NONE) => {
-                // move cursor to the top of the document
-                self.cursor_y = 0;
-                self.cursor_x = 0;
-                self.update_offsets();
+            (KeyCode::Char(':'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                self.enter_command();
+            }
+            (KeyCode::Char('v'), KeyModifiers::NONE) => self.enter_visual(Mode::Visual),
+            (KeyCode::Char('V'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                self.enter_visual(Mode::VisualLine);
+            }
+            (KeyCode::Char('v'), KeyModifiers::CONTROL) => self.enter_visual(Mode::VisualBlock),
+            (KeyCode::Char('/'), KeyModifiers::NONE) => {
+                self.enter_search(true);
+            }
+            (KeyCode::Char('?'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                self.enter_search(false);
+            }
+            (KeyCode::Char('n'), KeyModifiers::NONE) => {
+                self.search_next(self.search.forward);
+            }
+            (KeyCode::Char('N'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                self.search_next(!self.search.forward);
+            }
+            (KeyCode::Char('u'), KeyModifiers::NONE) => {
+                let cursor = self.docu.undo();
+                self.restore_cursor(cursor);
+            }
+            (KeyCode::Char('r'), KeyModifiers::CONTROL) => {
+                let cursor = self.docu.redo();
+                self.restore_cursor(cursor);
             }
 
             _ => {}
@@ -170,7 +287,9 @@ impl Editor {
                 self.status_bar.has_unsaved_changes = true;
             }
             (KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down, KeyModifiers::NONE) => {
-                self.handle_movement(key.code);
+                if let Some(motion) = Motion::from_key(key.code) {
+                    self.move_cursor(motion, 1);
+                }
             }
             _ => {}
         }
@@ -179,10 +298,11 @@ impl Editor {
     fn handle_writing_event(&mut self, key: KeyCode) {
         match key {
             KeyCode::Char(c) => {
-                let line = self.cursor_y;
-                let col = self.cursor_x;
-                self.docu.insert_char(c, line, col);
-                self.cursor_x += c.width().unwrap() as u16; // Move cursor right by the width of the character
+                // the cursor counts graphemes, a combining character joins the one before it instead of adding one
+                let end = self
+                    .docu
+                    .insert_str(self.cursor(), c.encode_utf8(&mut [0; 4]));
+                self.set_cursor(end);
             }
             KeyCode::Backspace => {
                 // IF THE CURSOR ISNT POINTING AT THE LEFT EDGE
@@ -195,8 +315,7 @@ impl Editor {
                 // IF THE CURSOR IS AT THE LEFT EDGE MOVE IT UP AND MERGE CURRENT LINE WITH LINE ABOVE
                 else if self.cursor_y > 0 {
                     let prev_line = self.cursor_y - 1;
-                    let prev_line_len =
-                        self.docu.lines[prev_line as usize].graphemes(true).count() as u16;
+                    let prev_line_len = self.docu.line_len(prev_line);
 
                     // Join the current line with the one above it.
                     self.docu.join_lines(self.cursor_y);
@@ -225,100 +344,146 @@ impl Editor {
         self.update_offsets();
     }
 
+    /// inserts pasted text in one go, as a single undo step, instead of typing it key by key
+    fn paste(&mut self, text: &str) {
+        // terminals send line breaks as carriage returns
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        match self.mode {
+            Mode::Command => self.command_line.insert_str(&text),
+            Mode::Search => {
+                self.search_line.insert_str(&text);
+                self.search_incrementally();
+            }
+            Mode::Confirm => {}
+            Mode::Normal | Mode::Insert | Mode::Visual | Mode::VisualLine | Mode::VisualBlock => {
+                if self.docu.read_only {
+                    self.status_bar.message = Some("file is read-only".to_string());
+                    return;
+                }
+                if self.is_visual() {
+                    self.enter_normal();
+                }
+                // typing before the paste is its own undo step, and so is typing after it
+                let insert_mode = self.mode == Mode::Insert;
+                self.docu.end_undo_group(self.cursor());
+                self.docu.begin_undo_group(self.cursor());
+                let end = self.docu.insert_str(self.cursor(), &text);
+                self.set_cursor(end);
+                self.docu.end_undo_group(end);
+                if insert_mode {
+                    self.docu.begin_undo_group(end);
+                }
+                self.status_bar.has_unsaved_changes = true;
+                self.update_offsets();
+            }
+        }
+    }
+
+    /// saves the document and reports how it went in the status bar, returns whether it was saved
+    fn save(&mut self) -> bool {
+        match self.docu.save() {
+            Ok(()) => {
+                self.status_bar.has_unsaved_changes = false;
+                self.status_bar.is_new = false;
+                self.status_bar.message =
+                    Some(format!("\"{}\" written", self.status_bar.file_name));
+                true
+            }
+            Err(e) => {
+                self.status_bar.message = Some(format!("Couldn't save: {e}"));
+                false
+            }
+        }
+    }
+
     fn enter_insert(&mut self) {
+        // everything typed until we leave insert mode is undone in one go
+        self.docu.begin_undo_group(self.cursor());
         self.status_bar.mode = Mode::Insert;
         self.mode = Mode::Insert;
     }
 
+    fn enter_command(&mut self) {
+        self.command_line.clear();
+        self.status_bar.mode = Mode::Command;
+        self.mode = Mode::Command;
+    }
+
     fn enter_normal(&mut self) {
+        self.docu.end_undo_group(self.cursor());
         self.status_bar.mode = Mode::Normal;
         self.mode = Mode::Normal;
     }
 
-    // moves cursor based on directional key pressed
-    fn handle_movement(&mut self, direction: KeyCode) {
-        match direction {
-            KeyCode::Char('h') | KeyCode::Left => {
-                if self.cursor_x > 0 {
-                    self.cursor_x -= 1;
-                }
-            }
-            KeyCode::Char('j') | KeyCode::Down => {
-                if self.cursor_y + 1 < self.docu.n_lines {
-                    self.cursor_y += 1;
-
-                    // Clamp cursor_x to new line length so we dont get an out of bounds error if we move from a short line to a long one
-                    let new_line = &self.docu.lines[self.cursor_y as usize];
-                    let new_len = new_line.graphemes(true).count() as u16;
-                    if self.cursor_x > new_len {
-                        self.cursor_x = new_len;
-                    }
-
-                    if self.cursor_y >= self.top_offset + self.term.height - 2 {
-                        self.top_offset += 1;
-                    }
-                }
-            }
-            KeyCode::Char('k') | KeyCode::Up => {
-                if self.cursor_y > 0 {
-                    self.cursor_y -= 1;
+    /// puts the cursor back where an undo/redo step says it was, if there was anything to undo
+    fn restore_cursor(&mut self, cursor: Option<Position>) {
+        if let Some(pos) = cursor {
+            self.set_cursor(self.docu.clamp(pos));
+            self.status_bar.has_unsaved_changes = !self.docu.is_saved();
+            self.update_offsets();
+        }
+    }
 
-                    // Clamp for safety
-                    let new_line = &self.docu.lines[self.cursor_y as usize];
-                    let new_len = new_line.graphemes(true).count() as u16;
-                    if self.cursor_x > new_len {
-                        self.cursor_x = new_len;
-                    }
+    pub fn cursor(&self) -> Position {
+        Position::new(self.cursor_y, self.cursor_x)
+    }
 
-                    if self.cursor_y < self.top_offset {
-                        self.top_offset = self.top_offset.saturating_sub(1);
-                    }
-                }
-            }
-            KeyCode::Char('l') | KeyCode::Right => {
-                let line = &self.docu.lines[self.cursor_y as usize];
-                let graphemes: Vec<&str> = line.graphemes(true).collect();
-                if (self.cursor_x as usize) < graphemes.len() {
-                    self.cursor_x += 1;
-                }
-            }
-            KeyCode::Char('w') => {
-                // move to the next word
-                let line = self.cursor_y;
-                let col = self.cursor_x;
-                (self.cursor_y, self.cursor_x) = self.docu.next_word(line, col);
-            }
-            KeyCode::Char('b') => {
-                // move to prev word
-                let line = self.cursor_y;
-                let col = self.cursor_x;
-                (self.cursor_y, self.cursor_x) = self.docu.prev_word(line, col);
-            }
-            _ => {}
-        }
-        self.update_offsets();
+    fn set_cursor(&mut self, pos: Position) {
+        self.cursor_y = pos.line;
+        self.cursor_x = pos.col;
     }
 
     fn update_top_offset(&mut self) {
         let margin = 4; // how many lines should be visible below the cursor at the bottom of the screen
-        let bottom_content = self.term.height - 2; // the bottom content area which includes the status bar and the line below it
+        let (bottom_content, _) = self.window_size(); // the rows of the window above its status line
+        let previous_top = self.top_offset;
         if self.cursor_y < self.top_offset {
             self.top_offset = self.cursor_y;
-        } else if self.cursor_y >= self.top_offset + bottom_content.saturating_sub(margin) {
+        } else {
+            let limit = bottom_content.saturating_sub(margin).max(1);
+            // every line takes at least a row, so lines further up than that can't be on screen
             self.top_offset = self
-                .cursor_y
-                .saturating_sub(bottom_content.saturating_sub(margin + 1));
+                .top_offset
+                .max((self.cursor_y + 1).saturating_sub(limit));
+            // wrapped lines take several rows, lines leave at the top until the cursor's row is far enough up
+            let mut rows = self.rows_above_cursor();
+            while rows >= limit && self.top_offset < self.cursor_y {
+                rows -= self.line_rows(self.top_offset);
+                self.top_offset += 1;
+            }
+        }
+        // the row a wrapped top line was shown from belongs to the line that was on top
+        if self.wrap && self.top_offset != previous_top {
+            self.left_offset = 0;
         }
     }
 
     fn update_left_offset(&mut self) {
-        let line_number_width = (self.docu.n_lines.to_string().len() + 3) as u16;
-        let available_width = self.term.width.saturating_sub(line_number_width);
+        if self.wrap {
+            self.update_top_row();
+            return;
+        }
+        let available_width = self.text_width();
 
         if self.cursor_x < self.left_offset {
             self.left_offset = self.cursor_x;
-        } else if self.cursor_x >= self.left_offset + available_width {
-            self.left_offset = self.cursor_x - available_width + 1;
+            return;
+        }
+        // the cells up to and including the cursor's have to fit, tabs and wide characters take several
+        let widths: Vec<usize> = self
+            .docu
+            .graphemes(self.cursor_y)
+            .take(self.cursor_x + 1)
+            .map(|g| display_width(&g))
+            .collect();
+        let past_end = usize::from(widths.len() == self.cursor_x); // the cursor after the last character
+        let mut used = widths[self.left_offset.min(widths.len())..]
+            .iter()
+            .sum::<usize>()
+            + past_end;
+        while used > available_width && self.left_offset < self.cursor_x {
+            used -= widths[self.left_offset];
+            self.left_offset += 1;
         }
     }
 
@@ -327,3 +492,15 @@ impl Editor {
         self.update_left_offset();
     }
 }
+
+/// the name shown in the status bar for a path
+pub fn display_name(file_path: &str) -> String {
+    if file_path.is_empty() {
+        return "[No Name]".to_string();
+    }
+    Path::new(file_path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(file_path)
+        .to_string()
+}
@@ -0,0 +1,237 @@
+//! Several open files. The current buffer lives in the editor's own fields, the others wait in `buffers`
+//! and are swapped in when switched to.
+
+use super::{Editor, display_name};
+use crate::buffer::Buffer;
+use crate::document::Document;
+use std::mem;
+
+impl Editor {
+    /// `:e path`, switches to the buffer editing `path` and opens one if there is none yet
+    /// returns false when the file couldn't be opened
+    pub(super) fn edit(&mut self, path: &str) -> bool {
+        if path == self.docu.file_name {
+            return true;
+        }
+        if let Some(idx) = self.buffers.iter().position(|b| b.docu.file_name == path) {
+            let buffer = self.buffers.remove(idx);
+            let previous = self.load_buffer(buffer);
+            self.buffers.push(previous);
+            return true;
+        }
+        match Document::new(path) {
+            Ok(docu) => {
+                let buffer = Buffer::new(self.next_buffer_number, docu);
+                self.next_buffer_number += 1;
+                let previous = self.load_buffer(buffer);
+                self.buffers.push(previous);
+                true
+            }
+            Err(e) => {
+                self.status_bar.message = Some(format!("Couldn't open \"{path}\": {e}"));
+                false
+            }
+        }
+    }
+
+    /// `:bn` and `:bp`, going around in the order of the buffer numbers
+    pub(super) fn cycle_buffer(&mut self, forward: bool) {
+        let current = self.buffer_number;
+        let numbers = self.buffers.iter().map(|b| b.number);
+        let target = if forward {
+            numbers
+                .clone()
+                .filter(|&n| n > current)
+                .min()
+                .or_else(|| numbers.min())
+        } else {
+            numbers
+                .clone()
+                .filter(|&n| n < current)
+                .max()
+                .or_else(|| numbers.max())
+        };
+        if let Some(number) = target {
+            self.switch_to_buffer(number);
+        }
+    }
+
+    /// `:b`, takes a buffer number or a part of a file name that only one buffer matches
+    pub(super) fn goto_buffer(&mut self, target: &str) {
+        match self.find_buffer(target) {
+            Ok(Some(number)) => self.switch_to_buffer(number),
+            Ok(None) => {}
+            Err(e) => self.status_bar.message = Some(e),
+        }
+    }
+
+    /// `:ls`, every buffer on one line since messages only get the status bar
+    pub(super) fn list_buffers(&mut self) {
+        let mut entries: Vec<(usize, String)> = self
+            .buffers
+            .iter()
+            .map(|b| {
+                let unsaved = if b.has_unsaved_changes { " [+]" } else { "" };
+                let name = display_name(&b.docu.file_name);
+                let line = b.cursor.line + 1;
+                (
+                    b.number,
+                    format!("{} \"{name}\"{unsaved} line {line}", b.number),
+                )
+            })
+            .collect();
+        let unsaved = if self.status_bar.has_unsaved_changes {
+            " [+]"
+        } else {
+            ""
+        };
+        entries.push((
+            self.buffer_number,
+            format!(
+                "{} %a \"{}\"{unsaved} line {}",
+                self.buffer_number,
+                self.status_bar.file_name,
+                self.cursor_y + 1
+            ),
+        ));
+        entries.sort_by_key(|(number, _)| *number);
+        let list: Vec<String> = entries.into_iter().map(|(_, entry)| entry).collect();
+        self.status_bar.message = Some(list.join(" │ "));
+    }
+
+    /// `:bd`, closes the current buffer or the one named, refusing to throw away unsaved changes without `!`
+    pub(super) fn delete_buffer(&mut self, target: Option<&str>, force: bool) {
+        let number = match target.map(|target| self.find_buffer(target)) {
+            None | Some(Ok(None)) => self.buffer_number,
+            Some(Ok(Some(number))) => number,
+            Some(Err(e)) => {
+                self.status_bar.message = Some(e);
+                return;
+            }
+        };
+        if number != self.buffer_number {
+            let idx = self.buffer_index(number);
+            if !force && self.buffers[idx].has_unsaved_changes {
+                self.status_bar.message = Some(format!(
+                    "No write since last change for buffer {number} (add ! to override)"
+                ));
+                return;
+            }
+            self.buffers.remove(idx);
+            self.forget_buffer(number);
+            return;
+        }
+        if !force && self.status_bar.has_unsaved_changes {
+            self.status_bar.message = Some(format!(
+                "No write since last change for buffer {number} (add ! to override)"
+            ));
+            return;
+        }
+        // the buffer `:bn` would go to takes the place of the closed one
+        let next = self
+            .buffers
+            .iter()
+            .map(|b| b.number)
+            .filter(|&n| n > number)
+            .min()
+            .or_else(|| self.buffers.iter().map(|b| b.number).max());
+        let buffer = if let Some(next) = next {
+            let idx = self.buffer_index(next);
+            self.buffers.remove(idx)
+        } else {
+            // closing the last buffer leaves an empty one behind, like starting without a file
+            self.scratch_buffer()
+        };
+        self.load_buffer(buffer);
+        self.forget_buffer(number);
+    }
+
+    /// the document of any buffer and whether it has unsaved changes, for drawing the windows and tabs showing it
+    pub fn buffer_state(&self, number: usize) -> (&Document, bool) {
+        if number == self.buffer_number {
+            (&self.docu, self.status_bar.has_unsaved_changes)
+        } else {
+            let buffer = &self.buffers[self.buffer_index(number)];
+            (&buffer.docu, buffer.has_unsaved_changes)
+        }
+    }
+
+    pub(super) fn hidden_buffer_mut(&mut self, number: usize) -> &mut Buffer {
+        let idx = self.buffer_index(number);
+        &mut self.buffers[idx]
+    }
+
+    /// a new empty buffer without a file
+    pub(super) fn scratch_buffer(&mut self) -> Buffer {
+        let buffer = Buffer::new(self.next_buffer_number, Document::scratch());
+        self.next_buffer_number += 1;
+        buffer
+    }
+
+    /// the first hidden buffer with unsaved changes, which `:q` shouldn't leave behind
+    pub(super) fn unsaved_buffer(&self) -> Option<&Buffer> {
+        self.buffers.iter().find(|b| b.has_unsaved_changes)
+    }
+
+    /// the number of the buffer `target` names, none when it's the current one
+    fn find_buffer(&self, target: &str) -> Result<Option<usize>, String> {
+        if let Ok(number) = target.parse::<usize>() {
+            return if number == self.buffer_number {
+                Ok(None)
+            } else if self.buffers.iter().any(|b| b.number == number) {
+                Ok(Some(number))
+            } else {
+                Err(format!("Buffer {number} does not exist"))
+            };
+        }
+        let current_matches = self.docu.file_name.contains(target);
+        let mut matches = self
+            .buffers
+            .iter()
+            .filter(|b| b.docu.file_name.contains(target))
+            .map(|b| b.number);
+        match (current_matches, matches.next(), matches.next()) {
+            (true, None, _) => Ok(None),
+            (false, Some(number), None) => Ok(Some(number)),
+            (false, None, _) => Err(format!("No matching buffer for {target}")),
+            _ => Err(format!("More than one match for {target}")),
+        }
+    }
+
+    pub(super) fn switch_to_buffer(&mut self, number: usize) {
+        let idx = self.buffer_index(number);
+        let buffer = self.buffers.remove(idx);
+        let previous = self.load_buffer(buffer);
+        self.buffers.push(previous);
+    }
+
+    fn buffer_index(&self, number: usize) -> usize {
+        self.buffers
+            .iter()
+            .position(|b| b.number == number)
+            .expect("buffer numbers come from the list of buffers")
+    }
+
+    /// makes `buffer` the current one and hands back the one that was current until now
+    pub(super) fn load_buffer(&mut self, buffer: Buffer) -> Buffer {
+        let previous = Buffer {
+            number: self.buffer_number,
+            docu: mem::replace(&mut self.docu, buffer.docu),
+            cursor: self.cursor(),
+            top_offset: self.top_offset,
+            left_offset: self.left_offset,
+            has_unsaved_changes: self.status_bar.has_unsaved_changes,
+        };
+        self.buffer_number = buffer.number;
+        self.set_cursor(buffer.cursor);
+        self.top_offset = buffer.top_offset;
+        self.left_offset = buffer.left_offset;
+        self.status_bar.file_name = display_name(&self.docu.file_name);
+        self.status_bar.has_unsaved_changes = buffer.has_unsaved_changes;
+        self.status_bar.read_only = self.docu.read_only;
+        self.status_bar.is_new = self.docu.is_new;
+        // the screen may have been resized since the buffer was last shown
+        self.update_offsets();
+        previous
+    }
+}
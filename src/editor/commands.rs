@@ -0,0 +1,209 @@
+//! Command mode: editing the command line and running the commands typed into it.
+
+use super::{Editor, display_name};
+use crate::command::Command;
+use crate::document::Document;
+use crate::file_format::{Encoding, LineEnding};
+use crate::window::Windows;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::path::Path;
+
+impl Editor {
+    pub(super) fn handle_command_mode_key_event(&mut self, key: KeyEvent) {
+        match (key.code, key.modifiers) {
+            (KeyCode::Esc, KeyModifiers::NONE) => {
+                self.enter_normal();
+            }
+            (KeyCode::Enter, KeyModifiers::NONE) => {
+                let input = self.command_line.take();
+                self.enter_normal();
+                self.execute_command(&input);
+            }
+            // backspacing over the ':' leaves command mode like in vim
+            (KeyCode::Backspace, KeyModifiers::NONE) if self.command_line.input.is_empty() => {
+                self.enter_normal();
+            }
+            (KeyCode::Backspace, KeyModifiers::NONE) => self.command_line.backspace(),
+            (KeyCode::Left, KeyModifiers::NONE) => self.command_line.move_left(),
+            (KeyCode::Right, KeyModifiers::NONE) => self.command_line.move_right(),
+            (KeyCode::Up, KeyModifiers::NONE) => self.command_line.history_prev(),
+            (KeyCode::Down, KeyModifiers::NONE) => self.command_line.history_next(),
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                self.command_line.insert(c);
+            }
+            _ => {}
+        }
+    }
+
+    fn execute_command(&mut self, input: &str) {
+        if input.trim().is_empty() {
+            return;
+        }
+        let command = match Command::parse(input) {
+            Ok(command) => command,
+            Err(e) => {
+                self.status_bar.message = Some(e);
+                return;
+            }
+        };
+        match command {
+            Command::Write {
+                path: Some(path),
+                force,
+            } if path != self.docu.file_name => self.write_to(&path, force),
+            Command::Write { .. } => {
+                self.save();
+            }
+            Command::Quit { force } => self.quit_window(force),
+            Command::WriteQuit => {
+                if self.save() {
+                    self.quit_window(false);
+                }
+            }
+            // another file opens in a buffer of its own, the current one stays open in the background
+            Command::Edit {
+                path: Some(path), ..
+            } if path != self.docu.file_name => {
+                self.edit(&path);
+            }
+            Command::Edit { path, force } => {
+                if !force && self.status_bar.has_unsaved_changes {
+                    self.status_bar.message =
+                        Some("No write since last change (add ! to override)".to_string());
+                    return;
+                }
+                // without a path the current file is reloaded from disk
+                let path = path.unwrap_or_else(|| self.docu.file_name.clone());
+                if path.is_empty() {
+                    self.status_bar.message = Some("No file name".to_string());
+                    return;
+                }
+                self.open(&path);
+            }
+            Command::GotoLine(line) => {
+                self.cursor_y = line.saturating_sub(1).min(self.docu.n_lines() - 1);
+                self.cursor_x = 0;
+                self.update_offsets();
+            }
+            Command::Set(option) => {
+                if let Err(e) = self.set_option(&option) {
+                    self.status_bar.message = Some(e);
+                }
+            }
+            Command::NoHighlight => {
+                self.search.set_pattern(None);
+            }
+            Command::Substitute(args) => {
+                self.substitute(args);
+            }
+            Command::BufferNext => self.cycle_buffer(true),
+            Command::BufferPrevious => self.cycle_buffer(false),
+            Command::Buffer(buffer) => self.goto_buffer(&buffer),
+            Command::ListBuffers => self.list_buffers(),
+            Command::BufferDelete { buffer, force } => {
+                self.delete_buffer(buffer.as_deref(), force);
+            }
+            Command::Split { path, vertical } => self.split_window(vertical, path.as_deref()),
+            Command::Close => self.close_window(),
+            Command::Only => self.only_window(),
+            Command::TabNew(path) => self.new_tab(path.as_deref()),
+            Command::TabNext => self.cycle_tab(true, None),
+            Command::TabPrevious => self.cycle_tab(false, None),
+            Command::TabClose => self.close_tab(),
+        }
+    }
+
+    pub(super) fn quit_unless_unsaved_buffers(&mut self, force: bool) {
+        match self.unsaved_buffer() {
+            Some(buffer) if !force => {
+                self.status_bar.message = Some(format!(
+                    "No write since last change for buffer {} (\"{}\")",
+                    buffer.number,
+                    display_name(&buffer.docu.file_name)
+                ));
+            }
+            _ => self.quit = true,
+        }
+    }
+
+    /// replaces the current document with the file at `path`
+    fn open(&mut self, path: &str) {
+        match Document::new(path) {
+            Ok(docu) => {
+                self.status_bar.file_name = display_name(path);
+                self.status_bar.has_unsaved_changes = false;
+                self.status_bar.read_only = docu.read_only;
+                self.status_bar.is_new = docu.is_new;
+                self.docu = docu;
+                self.cursor_x = 0;
+                self.cursor_y = 0;
+                self.top_offset = 0;
+                self.left_offset = 0;
+            }
+            Err(e) => {
+                self.status_bar.message = Some(format!("Couldn't open \"{path}\": {e}"));
+            }
+        }
+    }
+
+    /// `:w path` for a file other than the buffer's own, a buffer without a file takes the name
+    fn write_to(&mut self, path: &str, force: bool) {
+        if !force && Path::new(path).exists() {
+            self.status_bar.message = Some("File exists (add ! to override)".to_string());
+            return;
+        }
+        if !self.docu.file_name.is_empty() {
+            self.status_bar.message = Some(match self.docu.write_copy(path) {
+                Ok(()) => format!("\"{path}\" written"),
+                Err(e) => format!("Couldn't save: {e}"),
+            });
+            return;
+        }
+        self.docu.set_file_name(path);
+        self.status_bar.file_name = display_name(path);
+        // a failed save leaves the buffer without a name like before
+        if !self.save() {
+            self.docu.set_file_name("");
+            self.status_bar.file_name = display_name("");
+        }
+    }
+
+    /// handles `:set`, `wrap` and the options describing how the file is written
+    fn set_option(&mut self, option: &str) -> Result<(), String> {
+        if let "wrap" | "nowrap" = option {
+            self.wrap = option == "wrap";
+            // scrolling sideways and where a wrapped top line starts both use `left_offset`
+            self.left_offset = 0;
+            let other_tabs = self.tabs.iter_mut().flat_map(Windows::iter_mut);
+            for window in self.windows.iter_mut().chain(other_tabs) {
+                window.left_offset = 0;
+            }
+            for buffer in &mut self.buffers {
+                buffer.left_offset = 0;
+            }
+            self.update_offsets();
+            return Ok(());
+        }
+        let format = &mut self.docu.format;
+        let (name, value) = match option.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (option, None),
+        };
+        match (name, value) {
+            ("ff" | "fileformat", Some("unix")) => format.line_ending = LineEnding::Lf,
+            ("ff" | "fileformat", Some("dos")) => format.line_ending = LineEnding::Crlf,
+            ("eol" | "endofline", None) => format.trailing_newline = true,
+            ("noeol" | "noendofline", None) => format.trailing_newline = false,
+            ("bomb", None) => format.bom = true,
+            ("nobomb", None) if !format.encoding.needs_bom() => format.bom = false,
+            ("fenc" | "fileencoding", Some(value)) => {
+                format.encoding = Encoding::from_name(value)
+                    .ok_or_else(|| format!("Unknown encoding: {value}"))?;
+                format.bom |= format.encoding.needs_bom();
+            }
+            _ => return Err(format!("Invalid argument: {option}")),
+        }
+        self.status_bar.has_unsaved_changes = !self.docu.is_saved();
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+//! Mouse support: clicking places the cursor, dragging selects and the wheel scrolls.
+
+use super::Editor;
+use crate::document::Position;
+use crate::mode::Mode;
+use crate::screen::display_width;
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
+use unicode_segmentation::UnicodeSegmentation;
+
+const SCROLL_LINES: usize = 3; // how far one step of the wheel scrolls
+
+impl Editor {
+    pub(super) fn handle_mouse_event(&mut self, mouse: MouseEvent) {
+        // the prompts on the bottom row keep the cursor, the mouse only works on the document
+        if matches!(self.mode, Mode::Command | Mode::Search | Mode::Confirm) {
+            return;
+        }
+        if mouse.kind == MouseEventKind::Down(MouseButton::Left)
+            && mouse.row == 0
+            && self.has_tab_line()
+        {
+            if let Some(idx) = self.tab_at(mouse.column) {
+                if self.is_visual() {
+                    self.enter_normal();
+                }
+                self.goto_tab(idx);
+            }
+            return;
+        }
+        // clicking or scrolling another window makes it the current one, dragging stays in the current one
+        if matches!(
+            mouse.kind,
+            MouseEventKind::Down(_) | MouseEventKind::ScrollUp | MouseEventKind::ScrollDown
+        ) && let Some(id) = self.windows.at(mouse.column, mouse.row)
+            && id != self.windows.current
+        {
+            if self.is_visual() {
+                self.enter_normal();
+            }
+            self.goto_window(id);
+        }
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                let Some(pos) = self.position_at(mouse.column, mouse.row) else {
+                    return;
+                };
+                if self.is_visual() {
+                    self.enter_normal();
+                }
+                self.set_cursor(pos);
+                self.update_offsets();
+            }
+            MouseEventKind::Drag(MouseButton::Left) => {
+                let Some(pos) = self.position_at(mouse.column, mouse.row) else {
+                    return;
+                };
+                // the selection starts where the button was pressed, which is where the cursor still is
+                if !self.is_visual() {
+                    self.enter_normal();
+                    self.enter_visual(Mode::Visual);
+                }
+                self.set_cursor(pos);
+                self.update_offsets();
+            }
+            MouseEventKind::ScrollDown => self.scroll(SCROLL_LINES, true),
+            MouseEventKind::ScrollUp => self.scroll(SCROLL_LINES, false),
+            _ => {}
+        }
+    }
+
+    /// the document position shown at a screen cell of the current window, none for its status line and outside it
+    /// clicks on the line numbers go to the start of the line and clicks past its end go to the end, which is also
+    /// where a click lands on a line scrolled out of view to the left
+    fn position_at(&self, column: u16, row: u16) -> Option<Position> {
+        let rect = self.windows.current().rect;
+        if !rect.contains(column, row) || row >= rect.y + rect.height - 1 {
+            return None;
+        }
+        // clicks below the end of the document go to its last row
+        let rows = self.screen_rows();
+        let screen_row = rows.get(usize::from(row - rect.y)).or(rows.last())?;
+        let (line, cols) = (screen_row.line, screen_row.cols.clone());
+        let gutter_width = self.docu.n_lines().to_string().len() + 3;
+        let Some(mut x) = usize::from(column - rect.x).checked_sub(gutter_width) else {
+            return Some(self.docu.clamp(Position::new(line, cols.start)));
+        };
+        let mut col = cols.start;
+        // past the end of a wrapped row is the last character of the row, not the start of the next one
+        for grapheme in self
+            .docu
+            .line(line)
+            .graphemes(true)
+            .take(cols.end - 1)
+            .skip(cols.start)
+        {
+            // a click on either half of a wide character lands on it
+            let width = display_width(grapheme);
+            if x < width {
+                break;
+            }
+            x -= width;
+            col += 1;
+        }
+        Some(self.docu.clamp(Position::new(line, col)))
+    }
+
+    /// moves the view by `lines` without moving the cursor, unless it would go off screen
+    fn scroll(&mut self, lines: usize, down: bool) {
+        let last_line = self.docu.n_lines() - 1;
+        if self.wrap {
+            self.left_offset = 0;
+        }
+        self.top_offset = if down {
+            (self.top_offset + lines).min(last_line)
+        } else {
+            self.top_offset.saturating_sub(lines)
+        };
+        let bottom = self
+            .screen_rows()
+            .last()
+            .map_or(self.top_offset, |row| row.line);
+        let line = self.cursor_y.clamp(self.top_offset, bottom);
+        if line != self.cursor_y {
+            self.cursor_y = line;
+            self.cursor_x = self.cursor_x.min(self.docu.line_len(line));
+        }
+    }
+}
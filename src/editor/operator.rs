@@ -0,0 +1,290 @@
+//! Normal mode commands made of several keys, `[count] operator [count] motion` like `3dw` or `y2j`,
+//! and the operators themselves which visual mode shares.
+
+use super::Editor;
+use crate::document::Position;
+use crate::motion::Motion;
+use crate::operator::{Operator, PendingKeys};
+use crate::register::Registers;
+use crate::selection::Selection;
+use crate::text_object::{ObjectRange, TextObject};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+impl Editor {
+    /// feeds a key to the count/operator/motion parser, returns whether the key was used up by it
+    pub(super) fn handle_pending_keys(&mut self, key: KeyEvent) -> bool {
+        if self.pending.window_command {
+            let (_, count) = self.pending.take();
+            self.handle_window_key(key, count);
+            return true;
+        }
+        // the count stays for the window command, `3 Ctrl-w +` grows the window by three lines
+        if (key.code, key.modifiers) == (KeyCode::Char('w'), KeyModifiers::CONTROL)
+            && self.pending.operator.is_none()
+        {
+            self.pending.window_command = true;
+            return true;
+        }
+        if !matches!(key.modifiers, KeyModifiers::NONE | KeyModifiers::SHIFT) {
+            let had_operator = self.pending.operator.is_some();
+            self.pending = PendingKeys::default();
+            return had_operator;
+        }
+        if self.pending.naming_register {
+            match key.code {
+                KeyCode::Char(c) if Registers::is_valid(c) => self.pending.set_register(c),
+                _ => self.pending = PendingKeys::default(),
+            }
+            return true;
+        }
+        if let Some(around) = self.pending.text_object {
+            let register = self.pending.register();
+            let (operator, _) = self.pending.take();
+            if let (Some(operator), KeyCode::Char(c)) = (operator, key.code)
+                && let Some(object) = TextObject::from_char(c)
+            {
+                self.apply_to_text_object(operator, object, around, register);
+            }
+            return true;
+        }
+        if let KeyCode::Char(c) = key.code
+            && self.pending.push_digit(c)
+        {
+            return true;
+        }
+        if key.code == KeyCode::Char('"') && self.pending.operator.is_none() {
+            self.pending.naming_register = true;
+            return true;
+        }
+        if self.pending.operator.is_none()
+            && let KeyCode::Char(c @ ('p' | 'P')) = key.code
+        {
+            let register = self.pending.register();
+            let (_, count) = self.pending.take();
+            self.put(register, count, c == 'p');
+            return true;
+        }
+        if let Some(operator) = Operator::from_key(key.code) {
+            match self.pending.operator {
+                // doubling an operator like `dd` or `>>` acts on `count` whole lines
+                Some(pending) if pending == operator => {
+                    let register = self.pending.register();
+                    let (_, count) = self.pending.take();
+                    let first = self.cursor_y;
+                    let last = first.saturating_add(count - 1).min(self.docu.n_lines() - 1);
+                    self.apply_operator(operator, Selection::Lines { first, last }, register);
+                }
+                Some(_) => self.pending = PendingKeys::default(),
+                None => self.pending.set_operator(operator),
+            }
+            return true;
+        }
+        if self.pending.g_prefix {
+            self.pending.g_prefix = false;
+            return self.handle_g_key(key);
+        }
+        if key.code == KeyCode::Char('g') {
+            self.pending.g_prefix = true;
+            return true;
+        }
+        if let Some(motion) = Motion::from_key(key.code) {
+            self.apply_motion(motion);
+            return true;
+        }
+        // `i` and `a` after an operator start a text object instead of inserting
+        if self.pending.operator.is_some()
+            && let KeyCode::Char(c @ ('i' | 'a')) = key.code
+        {
+            self.pending.text_object = Some(c == 'a');
+            return true;
+        }
+        // anything else cancels a half typed command, the key itself is dropped if an operator was waiting
+        let had_operator = self.pending.operator.is_some();
+        self.pending = PendingKeys::default();
+        had_operator
+    }
+
+    /// the key after `g`: `gg`, `gt`/`gT` for tabs and `gj`/`gk` for display lines
+    fn handle_g_key(&mut self, key: KeyEvent) -> bool {
+        if let Some(motion) = Motion::from_g_key(key.code) {
+            self.apply_motion(motion);
+            return true;
+        }
+        if self.pending.operator.is_none()
+            && let KeyCode::Char(c @ ('t' | 'T')) = key.code
+        {
+            let count = self.pending.count();
+            self.pending.take();
+            self.cycle_tab(c == 't', count);
+            return true;
+        }
+        if self.pending.operator.is_none()
+            && let KeyCode::Char(c @ ('j' | 'k')) = key.code
+        {
+            let (_, count) = self.pending.take();
+            self.move_display_line(c == 'j', count);
+            return true;
+        }
+        let had_operator = self.pending.operator.is_some();
+        self.pending = PendingKeys::default();
+        had_operator
+    }
+
+    /// moves the cursor, or applies the operator waiting for a motion
+    fn apply_motion(&mut self, motion: Motion) {
+        let register = self.pending.register();
+        let (operator, count) = self.pending.take();
+        match operator {
+            Some(operator) => {
+                if let Some(selection) = self.motion_selection(operator, motion, count) {
+                    self.apply_operator(operator, selection, register);
+                }
+            }
+            None => self.move_cursor(motion, count),
+        }
+    }
+
+    pub(super) fn move_cursor(&mut self, motion: Motion, count: usize) {
+        let target = motion.target(&self.docu, self.cursor(), count);
+        self.set_cursor(target);
+        self.update_offsets();
+    }
+
+    /// the text an operator acts on when followed by `motion`, none if the motion doesn't cover anything
+    fn motion_selection(
+        &self,
+        operator: Operator,
+        motion: Motion,
+        count: usize,
+    ) -> Option<Selection> {
+        let from = self.cursor();
+        if motion.is_linewise() {
+            let to = motion.target(&self.docu, from, count);
+            return Some(Selection::Lines {
+                first: from.line.min(to.line),
+                last: from.line.max(to.line),
+            });
+        }
+        let mut to = if operator == Operator::Change
+            && motion == Motion::WordForward
+            && self.docu.word_end(from) != from
+        {
+            // `cw` on a word stops at the end of the word and leaves the space after it like in vim
+            let last_word = Motion::WordForward.target(&self.docu, from, count - 1);
+            self.docu.word_end(last_word)
+        } else {
+            motion.target(&self.docu, from, count)
+        };
+        // `dw` on the last word of a line doesn't take the line break with it
+        if motion == Motion::WordForward && to.line > from.line && to.col == 0 {
+            to = Position::new(to.line - 1, self.docu.line_len(to.line - 1));
+        }
+        // the motion's target itself isn't included
+        Selection::chars_between(&self.docu, from.min(to), from.max(to))
+    }
+
+    /// `ci"`, `da(` and the like
+    fn apply_to_text_object(
+        &mut self,
+        operator: Operator,
+        object: TextObject,
+        around: bool,
+        register: Option<char>,
+    ) {
+        match object.find(&self.docu, self.cursor(), around) {
+            Some(ObjectRange::Lines(first, last)) => {
+                self.apply_operator(operator, Selection::Lines { first, last }, register);
+            }
+            Some(ObjectRange::Chars(start, end)) => {
+                match Selection::chars_between(&self.docu, start, end) {
+                    Some(selection) => self.apply_operator(operator, selection, register),
+                    // changing an empty object like the inside of `""` starts typing in it
+                    None if operator == Operator::Change && !self.docu.read_only => {
+                        self.set_cursor(start);
+                        self.update_offsets();
+                        self.enter_insert();
+                    }
+                    None => {}
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// `register` is where yanked or deleted text goes, none for the unnamed register
+    pub(super) fn apply_operator(
+        &mut self,
+        operator: Operator,
+        selection: Selection,
+        register: Option<char>,
+    ) {
+        if operator != Operator::Yank && self.docu.read_only {
+            self.status_bar.message = Some("file is read-only".to_string());
+            return;
+        }
+        match operator {
+            Operator::Yank => self.yank(selection, register),
+            Operator::Delete => self.delete(selection, register, false),
+            Operator::Change => self.delete(selection, register, true),
+            Operator::Indent => self.shift_lines(selection, true),
+            Operator::Dedent => self.shift_lines(selection, false),
+        }
+    }
+
+    fn yank(&mut self, selection: Selection, name: Option<char>) {
+        let register = selection.yank(&self.docu);
+        self.status_bar.message = register.yank_message();
+        self.store_register(name, register, false);
+        // yanking lines keeps the column, anything else goes to the start of what was yanked
+        let target = match selection {
+            Selection::Lines { first, .. } => {
+                Position::new(first, self.cursor_x.min(self.docu.line_len(first)))
+            }
+            _ => selection.start(),
+        };
+        self.set_cursor(target);
+        self.update_offsets();
+    }
+
+    /// `d` removes the text, `c` also starts inserting where it was
+    fn delete(&mut self, selection: Selection, name: Option<char>, change: bool) {
+        self.store_register(name, selection.yank(&self.docu), true);
+        self.docu.begin_undo_group(self.cursor());
+        if change && let Selection::Lines { first, last } = selection {
+            // changing lines keeps one empty line to type into
+            self.docu.delete_range(
+                Position::new(first, 0),
+                Position::new(last, self.docu.line_len(last)),
+            );
+        } else {
+            selection.delete(&mut self.docu);
+        }
+        let start = selection.start();
+        let line = start.line.min(self.docu.n_lines() - 1);
+        self.set_cursor(Position::new(line, start.col.min(self.docu.line_len(line))));
+        self.status_bar.has_unsaved_changes = true;
+        self.update_offsets();
+        if change {
+            // the group stays open so what gets typed is undone together with the deletion
+            self.enter_insert();
+        } else {
+            self.docu.end_undo_group(self.cursor());
+        }
+    }
+
+    /// `>` and `<` indent or dedent every line the text touches
+    fn shift_lines(&mut self, selection: Selection, right: bool) {
+        self.docu.begin_undo_group(self.cursor());
+        for line in selection.first_line()..=selection.last_line() {
+            if right {
+                self.docu.indent(line);
+            } else {
+                self.docu.dedent(line);
+            }
+        }
+        self.set_cursor(Position::new(selection.first_line(), 0));
+        self.docu.end_undo_group(self.cursor());
+        self.status_bar.has_unsaved_changes = true;
+        self.update_offsets();
+    }
+}
@@ -0,0 +1,127 @@
+//! Storing yanked and deleted text in registers, keeping the system clipboard in sync, and `p`/`P`.
+
+use super::Editor;
+use crate::clipboard;
+use crate::document::Position;
+use crate::register::{Register, RegisterKind};
+
+impl Editor {
+    /// keeps yanked or deleted text in the register `name`, `+` and `*` also go to the system clipboard
+    pub(super) fn store_register(&mut self, name: Option<char>, register: Register, deleted: bool) {
+        if let Some(name @ ('+' | '*')) = name
+            && let Err(e) = clipboard::copy(&register.text, name == '*')
+        {
+            self.status_bar.message = Some(format!("Couldn't copy to the clipboard: {e}"));
+        }
+        self.registers.store(name, register, deleted);
+    }
+
+    /// the contents of register `name`, `+` and `*` are read from the system clipboard when possible
+    fn fetch_register(&self, name: Option<char>) -> Option<Register> {
+        let stored = self.registers.get(name).cloned();
+        let Some(name @ ('+' | '*')) = name else {
+            return stored;
+        };
+        let Some(text) = clipboard::paste(name == '*') else {
+            return stored;
+        };
+        match stored {
+            // text we copied ourselves keeps its kind, so blocks come back as blocks
+            Some(register) if register.text == text => Some(register),
+            _ => Some(Register {
+                kind: if text.ends_with('\n') {
+                    RegisterKind::Lines
+                } else {
+                    RegisterKind::Chars
+                },
+                text,
+            }),
+        }
+    }
+
+    /// puts `count` copies of the register after the cursor, or before it for `P`
+    /// lines go below or above the current line, characters go next to the cursor and blocks go in as columns
+    pub(super) fn put(&mut self, name: Option<char>, count: usize, after: bool) {
+        if self.docu.read_only {
+            self.status_bar.message = Some("file is read-only".to_string());
+            return;
+        }
+        let Some(register) = self.fetch_register(name) else {
+            self.status_bar.message = Some(format!("Nothing in register {}", name.unwrap_or('"')));
+            return;
+        };
+        self.docu.begin_undo_group(self.cursor());
+        let cursor = match register.kind {
+            RegisterKind::Lines => self.put_lines(&register.text.repeat(count), after),
+            RegisterKind::Chars => self.put_chars(&register.text.repeat(count), after),
+            RegisterKind::Block => self.put_block(&register.text, count, after),
+        };
+        self.set_cursor(cursor);
+        self.docu.end_undo_group(cursor);
+        self.status_bar.has_unsaved_changes = true;
+        self.update_offsets();
+    }
+
+    fn put_lines(&mut self, text: &str, after: bool) -> Position {
+        let line = if after {
+            self.cursor_y + 1
+        } else {
+            self.cursor_y
+        };
+        if line < self.docu.n_lines() {
+            self.docu.insert_str(Position::new(line, 0), text);
+        } else {
+            // below the last line, which has no line break to insert after
+            let last = self.docu.n_lines() - 1;
+            let end = Position::new(last, self.docu.line_len(last));
+            self.docu.insert_str(
+                end,
+                &format!("\n{}", text.strip_suffix('\n').unwrap_or(text)),
+            );
+        }
+        Position::new(line, 0)
+    }
+
+    /// the cursor ends on the last character put, or at the start when it spans several lines like in vim
+    fn put_chars(&mut self, text: &str, after: bool) -> Position {
+        let line_len = self.docu.line_len(self.cursor_y);
+        let col = if after && line_len > 0 {
+            (self.cursor_x + 1).min(line_len)
+        } else {
+            self.cursor_x
+        };
+        let start = Position::new(self.cursor_y, col);
+        let end = self.docu.insert_str(start, text);
+        if text.contains('\n') {
+            start
+        } else {
+            Position::new(end.line, end.col.saturating_sub(1))
+        }
+    }
+
+    /// each row of the block goes into the next line at the same column, lines are added or padded as needed
+    fn put_block(&mut self, text: &str, count: usize, after: bool) -> Position {
+        let line_len = self.docu.line_len(self.cursor_y);
+        let col = if after && line_len > 0 {
+            (self.cursor_x + 1).min(line_len)
+        } else {
+            self.cursor_x
+        };
+        for (i, row) in text.split('\n').enumerate() {
+            let line = self.cursor_y + i;
+            if line >= self.docu.n_lines() {
+                let last = self.docu.n_lines() - 1;
+                self.docu
+                    .insert_str(Position::new(last, self.docu.line_len(last)), "\n");
+            }
+            let len = self.docu.line_len(line);
+            if len < col {
+                self.docu
+                    .insert_str(Position::new(line, len), &" ".repeat(col - len));
+            }
+            self.docu
+                .insert_str(Position::new(line, col), &row.repeat(count));
+        }
+        Position::new(self.cursor_y, col)
+    }
+}
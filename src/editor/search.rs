@@ -0,0 +1,102 @@
+//! Search mode: the `/` and `?` prompt and jumping between matches.
+
+use super::Editor;
+use crate::mode::Mode;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+impl Editor {
+    pub(super) fn enter_search(&mut self, forward: bool) {
+        self.search.start(forward, self.cursor());
+        self.search_line.clear();
+        self.status_bar.mode = Mode::Search;
+        self.mode = Mode::Search;
+    }
+
+    pub(super) fn handle_search_mode_key_event(&mut self, key: KeyEvent) {
+        match (key.code, key.modifiers) {
+            (KeyCode::Esc, KeyModifiers::NONE) => {
+                self.search.cancel();
+                let origin = self.search.origin;
+                self.set_cursor(origin);
+                self.update_offsets();
+                self.enter_normal();
+            }
+            (KeyCode::Enter, KeyModifiers::NONE) => {
+                let pattern = self.search_line.take();
+                self.enter_normal();
+                if pattern.is_empty() {
+                    // an empty search repeats the last one like in vim
+                    self.search.cancel();
+                    self.search_next(self.search.forward);
+                } else if self
+                    .search
+                    .find(&self.docu, self.search.origin, self.search.forward)
+                    .is_none()
+                {
+                    self.status_bar.message = Some(format!("Pattern not found: {pattern}"));
+                }
+            }
+            (KeyCode::Backspace, KeyModifiers::NONE) if self.search_line.input.is_empty() => {
+                self.search.cancel();
+                self.enter_normal();
+            }
+            (KeyCode::Backspace, KeyModifiers::NONE) => {
+                self.search_line.backspace();
+                self.search_incrementally();
+            }
+            (KeyCode::Left, KeyModifiers::NONE) => self.search_line.move_left(),
+            (KeyCode::Right, KeyModifiers::NONE) => self.search_line.move_right(),
+            (KeyCode::Up, KeyModifiers::NONE) => {
+                self.search_line.history_prev();
+                self.search_incrementally();
+            }
+            (KeyCode::Down, KeyModifiers::NONE) => {
+                self.search_line.history_next();
+                self.search_incrementally();
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                self.search_line.insert(c);
+                self.search_incrementally();
+            }
+            _ => {}
+        }
+    }
+
+    /// moves the cursor to the first match of what has been typed so far, or back to where the search started
+    pub(super) fn search_incrementally(&mut self) {
+        let input = &self.search_line.input;
+        self.search
+            .set_pattern((!input.is_empty()).then(|| input.clone()));
+        let origin = self.search.origin;
+        let target = self
+            .search
+            .find(&self.docu, origin, self.search.forward)
+            .map_or(origin, |(pos, _)| pos);
+        self.set_cursor(target);
+        self.update_offsets();
+    }
+
+    /// `n` and `N`, jumps to the next match of the last pattern in the given direction
+    pub(super) fn search_next(&mut self, forward: bool) {
+        let Some(pattern) = self.search.pattern().map(str::to_string) else {
+            self.status_bar.message = Some("No previous search pattern".to_string());
+            return;
+        };
+        match self.search.find(&self.docu, self.cursor(), forward) {
+            Some((pos, wrapped)) => {
+                self.set_cursor(pos);
+                self.update_offsets();
+                if wrapped {
+                    self.status_bar.message = Some(if forward {
+                        "search hit BOTTOM, continuing at TOP".to_string()
+                    } else {
+                        "search hit TOP, continuing at BOTTOM".to_string()
+                    });
+                }
+            }
+            None => {
+                self.status_bar.message = Some(format!("Pattern not found: {pattern}"));
+            }
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! `:s` and confirming its matches one by one.
+
+use super::Editor;
+use crate::document::Position;
+use crate::mode::Mode;
+use crate::substitute::{SubstituteArgs, Substitution};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+impl Editor {
+    pub(super) fn substitute(&mut self, mut args: SubstituteArgs) {
+        if self.docu.read_only {
+            self.status_bar.message = Some("file is read-only".to_string());
+            return;
+        }
+        // an empty pattern reuses the last search like in vim
+        if args.pattern.is_empty() {
+            let Some(pattern) = self.search.pattern() else {
+                self.status_bar.message = Some("No previous regular expression".to_string());
+                return;
+            };
+            args.pattern = pattern.to_string();
+        }
+        let (first_line, last_line) = args.range.resolve(self.cursor_y, self.docu.n_lines());
+        let mut substitution = match Substitution::new(&args, first_line, last_line) {
+            Ok(substitution) => substitution,
+            Err(e) => {
+                self.status_bar.message = Some(e);
+                return;
+            }
+        };
+        // `n` and the match highlighting pick up the substituted pattern
+        self.search.set_pattern(Some(args.pattern.clone()));
+
+        self.docu.begin_undo_group(self.cursor());
+        if substitution.confirm && substitution.find_next(&self.docu) {
+            self.show_current_match(&substitution);
+            self.substitution = Some(substitution);
+            self.status_bar.mode = Mode::Confirm;
+            self.mode = Mode::Confirm;
+        } else {
+            substitution.replace_all(&mut self.docu);
+            self.finish_substitution(&substitution, &args.pattern);
+        }
+    }
+
+    pub(super) fn handle_confirm_mode_key_event(&mut self, key: KeyEvent) {
+        let Some(mut substitution) = self.substitution.take() else {
+            self.enter_normal();
+            return;
+        };
+        let done = match (key.code, key.modifiers) {
+            (KeyCode::Char('y'), KeyModifiers::NONE) => {
+                substitution.replace_current(&mut self.docu);
+                !substitution.find_next(&self.docu)
+            }
+            (KeyCode::Char('n'), KeyModifiers::NONE) => {
+                substitution.skip_current(&self.docu);
+                !substitution.find_next(&self.docu)
+            }
+            (KeyCode::Char('a'), KeyModifiers::NONE) => {
+                substitution.replace_all(&mut self.docu);
+                true
+            }
+            (KeyCode::Char('l'), KeyModifiers::NONE) => {
+                substitution.replace_current(&mut self.docu);
+                true
+            }
+            (KeyCode::Char('q'), KeyModifiers::NONE) | (KeyCode::Esc, _) => true,
+            _ => false,
+        };
+        if done {
+            let pattern = self.search.pattern().unwrap_or_default().to_string();
+            self.finish_substitution(&substitution, &pattern);
+            self.enter_normal();
+        } else {
+            self.show_current_match(&substitution);
+            self.substitution = Some(substitution);
+        }
+    }
+
+    fn show_current_match(&mut self, substitution: &Substitution) {
+        if let Some((line, cols)) = substitution.current_match(&self.docu) {
+            self.set_cursor(Position::new(line, cols.start));
+            self.update_offsets();
+        }
+    }
+
+    fn finish_substitution(&mut self, substitution: &Substitution, pattern: &str) {
+        self.docu.end_undo_group(self.cursor());
+        if substitution.substitutions == 0 {
+            self.status_bar.message = Some(format!("Pattern not found: {pattern}"));
+            return;
+        }
+        if let Some(pos) = substitution.last_replaced {
+            self.set_cursor(pos);
+            self.update_offsets();
+        }
+        self.status_bar.has_unsaved_changes = true;
+        self.status_bar.message = Some(substitution.summary());
+    }
+}
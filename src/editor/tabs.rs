@@ -0,0 +1,110 @@
+//! Tab pages, each with a window layout of its own. The current tab's windows are in `windows`,
+//! the others wait in `tabs` in the order they're shown on the tab line.
+
+use super::{Editor, display_name};
+use crate::window::Windows;
+use std::mem;
+
+impl Editor {
+    /// `:tabnew`, a tab after the current one with a single window showing `path` or an empty buffer
+    /// an empty buffer is also what the tab gets when `path` can't be opened
+    pub(super) fn new_tab(&mut self, path: Option<&str>) {
+        self.store_window();
+        if !path.is_some_and(|path| self.edit(path)) {
+            let scratch = self.scratch_buffer();
+            let previous = self.load_buffer(scratch);
+            self.buffers.push(previous);
+        }
+        let windows = Windows::new(self.buffer_number);
+        self.tabs
+            .insert(self.tab, mem::replace(&mut self.windows, windows));
+        self.tab += 1;
+        self.arrange_windows();
+    }
+
+    /// `gt` and `gT`, a count with `gt` goes to that tab and with `gT` goes back that many tabs
+    pub(super) fn cycle_tab(&mut self, forward: bool, count: Option<usize>) {
+        let n_tabs = self.tabs.len() + 1;
+        let idx = match (forward, count) {
+            (true, Some(count)) if count <= n_tabs => count - 1,
+            (true, Some(_)) => return,
+            (true, None) => (self.tab + 1) % n_tabs,
+            (false, count) => {
+                let steps = count.unwrap_or(1) % n_tabs;
+                (self.tab + n_tabs - steps) % n_tabs
+            }
+        };
+        self.goto_tab(idx);
+    }
+
+    /// `:tabclose`, the buffers shown in the tab stay open in the background
+    pub(super) fn close_tab(&mut self) {
+        if self.tabs.is_empty() {
+            self.status_bar.message = Some("Cannot close last tab page".to_string());
+            return;
+        }
+        // like in vim the tab to the right takes over, or the one to the left for the last tab
+        let idx = self.tab.min(self.tabs.len() - 1);
+        self.windows = self.tabs.remove(idx);
+        self.tab = idx;
+        self.arrange_windows();
+        self.load_window();
+    }
+
+    /// what the tab line says about every tab: its number, the file of its current window and whether anything shown is unsaved
+    pub fn tab_labels(&self) -> Vec<String> {
+        (0..=self.tabs.len())
+            .map(|idx| {
+                let (windows, buffer) = if idx == self.tab {
+                    (&self.windows, self.buffer_number)
+                } else {
+                    let windows = &self.tabs[if idx < self.tab { idx } else { idx - 1 }];
+                    (windows, windows.current().buffer)
+                };
+                let name = display_name(&self.buffer_state(buffer).0.file_name);
+                let unsaved = windows.iter().any(|window| {
+                    let buffer = if window.id == windows.current && idx == self.tab {
+                        self.buffer_number
+                    } else {
+                        window.buffer
+                    };
+                    self.buffer_state(buffer).1
+                });
+                let unsaved = if unsaved { " [+]" } else { "" };
+                format!(" {} {name}{unsaved} ", idx + 1)
+            })
+            .collect()
+    }
+
+    pub fn current_tab(&self) -> usize {
+        self.tab
+    }
+
+    /// the tab whose label is at a column of the tab line
+    pub(super) fn tab_at(&self, column: u16) -> Option<usize> {
+        let mut end = 0;
+        self.tab_labels().iter().position(|label| {
+            end += label.chars().count();
+            usize::from(column) < end
+        })
+    }
+
+    /// the tab line only shows once there is more than one tab, like in vim
+    pub fn has_tab_line(&self) -> bool {
+        !self.tabs.is_empty()
+    }
+
+    pub(super) fn goto_tab(&mut self, idx: usize) {
+        if idx == self.tab || idx > self.tabs.len() {
+            return;
+        }
+        self.store_window();
+        let current = mem::replace(&mut self.windows, Windows::new(self.buffer_number));
+        self.tabs.insert(self.tab, current);
+        self.windows = self.tabs.remove(idx);
+        self.tab = idx;
+        // the tab may have been laid out for another terminal size, or before the tab line showed up
+        self.arrange_windows();
+        self.load_window();
+    }
+}
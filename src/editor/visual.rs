@@ -0,0 +1,159 @@
+//! Visual modes: selecting text with `v`, `V` and `Ctrl-v` and operating on the selection.
+
+use super::Editor;
+use crate::document::Position;
+use crate::mode::Mode;
+use crate::motion::Motion;
+use crate::operator::{Operator, PendingKeys};
+use crate::register::Registers;
+use crate::selection::Selection;
+use crate::text_object::{ObjectRange, TextObject};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+impl Editor {
+    /// starts selecting from the cursor, or switches between the visual modes while already selecting
+    pub(super) fn enter_visual(&mut self, mode: Mode) {
+        if self.mode == mode {
+            // pressing the key of the current visual mode again leaves it like in vim
+            self.enter_normal();
+            return;
+        }
+        if !self.is_visual() {
+            self.visual_anchor = self.cursor();
+        }
+        self.status_bar.mode = mode;
+        self.mode = mode;
+    }
+
+    pub fn is_visual(&self) -> bool {
+        matches!(
+            self.mode,
+            Mode::Visual | Mode::VisualLine | Mode::VisualBlock
+        )
+    }
+
+    /// what is selected right now, if in a visual mode
+    pub fn selection(&self) -> Option<Selection> {
+        Selection::new(self.mode, self.visual_anchor, self.cursor())
+    }
+
+    pub(super) fn handle_visual_mode_key_event(&mut self, key: KeyEvent) {
+        if self.pending.naming_register {
+            match key.code {
+                KeyCode::Char(c) if Registers::is_valid(c) => self.pending.set_register(c),
+                _ => self.pending = PendingKeys::default(),
+            }
+            return;
+        }
+        if let Some(around) = self.pending.text_object {
+            self.pending.take();
+            if let KeyCode::Char(c) = key.code
+                && let Some(object) = TextObject::from_char(c)
+            {
+                self.select_text_object(object, around);
+            }
+            return;
+        }
+        if let KeyCode::Char(c) = key.code
+            && key.modifiers == KeyModifiers::NONE
+            && self.pending.push_digit(c)
+        {
+            return;
+        }
+        if key.code == KeyCode::Char('"') {
+            self.pending.naming_register = true;
+            return;
+        }
+        if self.pending.g_prefix {
+            let (_, count) = self.pending.take();
+            if let Some(motion) = Motion::from_g_key(key.code) {
+                self.move_cursor(motion, count);
+            } else if let KeyCode::Char(c @ ('j' | 'k')) = key.code {
+                self.move_display_line(c == 'j', count);
+            }
+            return;
+        }
+        if key.code == KeyCode::Char('g') {
+            self.pending.g_prefix = true;
+            return;
+        }
+        // counts only go with motions here, the operators act on the selection
+        let register = self.pending.register();
+        let (_, count) = self.pending.take();
+        if matches!(key.modifiers, KeyModifiers::NONE | KeyModifiers::SHIFT)
+            && let Some(motion) = Motion::from_key(key.code)
+        {
+            self.move_cursor(motion, count);
+            return;
+        }
+        match (key.code, key.modifiers) {
+            (KeyCode::Esc, KeyModifiers::NONE) => {
+                self.enter_normal();
+            }
+            (KeyCode::Char('v'), KeyModifiers::NONE) => self.enter_visual(Mode::Visual),
+            (KeyCode::Char('V'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                self.enter_visual(Mode::VisualLine);
+            }
+            (KeyCode::Char('v'), KeyModifiers::CONTROL) => self.enter_visual(Mode::VisualBlock),
+            (KeyCode::Char(c @ ('i' | 'a')), KeyModifiers::NONE) => {
+                self.pending.text_object = Some(c == 'a');
+            }
+            (KeyCode::Char('o'), KeyModifiers::NONE) => {
+                // jump to the other end of the selection
+                let cursor = self.cursor();
+                self.set_cursor(self.visual_anchor);
+                self.visual_anchor = cursor;
+                self.update_offsets();
+            }
+            (KeyCode::Char('y'), KeyModifiers::NONE) => {
+                self.operate_on_selection(Operator::Yank, register);
+            }
+            (KeyCode::Char('d' | 'x'), KeyModifiers::NONE) => {
+                self.operate_on_selection(Operator::Delete, register);
+            }
+            (KeyCode::Char('c' | 's'), KeyModifiers::NONE) => {
+                self.operate_on_selection(Operator::Change, register);
+            }
+            (KeyCode::Char('>'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                self.operate_on_selection(Operator::Indent, register);
+            }
+            (KeyCode::Char('<'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                self.operate_on_selection(Operator::Dedent, register);
+            }
+            _ => {}
+        }
+    }
+
+    /// `viw`, `va{` and the like select the object, switching between charwise and linewise as needed
+    fn select_text_object(&mut self, object: TextObject, around: bool) {
+        let mode = match object.find(&self.docu, self.cursor(), around) {
+            Some(ObjectRange::Lines(first, last)) => {
+                self.visual_anchor = Position::new(first, 0);
+                self.set_cursor(Position::new(last, 0));
+                Mode::VisualLine
+            }
+            Some(ObjectRange::Chars(start, end)) => {
+                let Some(Selection::Chars { start, end }) =
+                    Selection::chars_between(&self.docu, start, end)
+                else {
+                    return;
+                };
+                self.visual_anchor = start;
+                self.set_cursor(end);
+                Mode::Visual
+            }
+            None => return,
+        };
+        self.status_bar.mode = mode;
+        self.mode = mode;
+        self.update_offsets();
+    }
+
+    fn operate_on_selection(&mut self, operator: Operator, register: Option<char>) {
+        let Some(selection) = self.selection() else {
+            return;
+        };
+        self.enter_normal();
+        self.apply_operator(operator, selection, register);
+    }
+}
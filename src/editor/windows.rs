@@ -0,0 +1,199 @@
+//! Split windows: `:split`, `:vsplit` and the `Ctrl-w` commands moving between windows and resizing them.
+
+use super::Editor;
+use crate::window::{Direction, Rect, Windows};
+use crossterm::event::{KeyCode, KeyEvent};
+
+impl Editor {
+    /// the keys after `Ctrl-w`, a count before `Ctrl-w` repeats moves and says how much to resize by
+    pub(super) fn handle_window_key(&mut self, key: KeyEvent, count: usize) {
+        let amount = u16::try_from(count).unwrap_or(u16::MAX);
+        match key.code {
+            KeyCode::Char('s' | 'S') => self.split_window(false, None),
+            KeyCode::Char('v') => self.split_window(true, None),
+            KeyCode::Char('w') => self.goto_window(self.windows.cycle(true, count)),
+            KeyCode::Char('W') => self.goto_window(self.windows.cycle(false, count)),
+            KeyCode::Char('h') | KeyCode::Left => self.move_to_window(Direction::Left),
+            KeyCode::Char('j') | KeyCode::Down => self.move_to_window(Direction::Down),
+            KeyCode::Char('k') | KeyCode::Up => self.move_to_window(Direction::Up),
+            KeyCode::Char('l') | KeyCode::Right => self.move_to_window(Direction::Right),
+            KeyCode::Char('c') => self.close_window(),
+            KeyCode::Char('q') => self.quit_window(false),
+            KeyCode::Char('o') => self.only_window(),
+            KeyCode::Char('+') => self.resize_window(false, amount, true),
+            KeyCode::Char('-') => self.resize_window(false, amount, false),
+            KeyCode::Char('>') => self.resize_window(true, amount, true),
+            KeyCode::Char('<') => self.resize_window(true, amount, false),
+            KeyCode::Char('=') => {
+                self.windows.equalize();
+                self.arrange_windows();
+            }
+            _ => {}
+        }
+    }
+
+    /// `:split` and `:vsplit`, optionally opening a file in the new window
+    pub(super) fn split_window(&mut self, vertical: bool, path: Option<&str>) {
+        self.store_window();
+        if !self.windows.split(vertical) {
+            self.status_bar.message = Some("Not enough room".to_string());
+            return;
+        }
+        self.arrange_windows();
+        if let Some(path) = path {
+            self.edit(path);
+        }
+    }
+
+    /// `:close`, the buffer stays open in the background
+    pub(super) fn close_window(&mut self) {
+        let current = self.windows.current;
+        if !self.windows.close(current) {
+            self.status_bar.message = Some("Cannot close last window".to_string());
+            return;
+        }
+        self.arrange_windows();
+        self.load_window();
+    }
+
+    /// `:q` closes the window, then the tab once it's the tab's last window, and quits once it's the last tab
+    pub(super) fn quit_window(&mut self, force: bool) {
+        if self.windows.len() > 1 {
+            self.close_window();
+        } else if self.has_tab_line() {
+            self.close_tab();
+        } else if force || !self.status_bar.has_unsaved_changes {
+            self.quit_unless_unsaved_buffers(force);
+        } else {
+            self.status_bar.message =
+                Some("No write since last change (add ! to override)".to_string());
+        }
+    }
+
+    /// `:only`
+    pub(super) fn only_window(&mut self) {
+        self.windows.only();
+        self.arrange_windows();
+    }
+
+    /// makes window `id` the current one, bringing in its buffer, cursor and offsets
+    pub(super) fn goto_window(&mut self, id: usize) {
+        if id == self.windows.current {
+            return;
+        }
+        self.store_window();
+        self.windows.current = id;
+        self.load_window();
+    }
+
+    /// how many lines and columns the current window has for text
+    pub(super) fn window_size(&self) -> (usize, usize) {
+        let rect = self.windows.current().rect;
+        let height = usize::from(rect.height.saturating_sub(1)); // the status line is the last row
+        (height, usize::from(rect.width))
+    }
+
+    /// lays the windows out on the screen again, after splitting, closing, resizing or a terminal resize
+    pub(super) fn arrange_windows(&mut self) {
+        // the bottom row is for the command line and the top row for the tab line if there is one
+        let tab_line = u16::from(self.has_tab_line());
+        let area = Rect {
+            x: 0,
+            y: tab_line,
+            width: self.term.width,
+            height: self.term.height.saturating_sub(1 + tab_line),
+        };
+        self.windows.arrange(area);
+        self.update_offsets();
+    }
+
+    /// works out the syntax highlighting of the lines about to be shown in every window, it's cached until a line changes
+    pub(super) fn highlight_visible_lines(&mut self) {
+        let current = self.windows.current;
+        let visible: Vec<(usize, usize, usize, u16, u16)> = self
+            .windows
+            .iter()
+            .map(|window| {
+                let (width, height) = (window.rect.width, window.rect.height);
+                if window.id == current {
+                    let (top_offset, left_offset) = (self.top_offset, self.left_offset);
+                    (self.buffer_number, top_offset, left_offset, width, height)
+                } else {
+                    (
+                        window.buffer,
+                        window.top_offset,
+                        window.left_offset,
+                        width,
+                        height,
+                    )
+                }
+            })
+            .collect();
+        for (buffer, top_offset, left_offset, width, height) in visible {
+            let rows = usize::from(height.saturating_sub(1));
+            // only as much of a long line as can be on screen, every grapheme takes at least one cell
+            let shown = left_offset + usize::from(width) * if self.wrap { rows } else { 1 };
+            let docu = if buffer == self.buffer_number {
+                &mut self.docu
+            } else {
+                &mut self.hidden_buffer_mut(buffer).docu
+            };
+            for doc_row in top_offset..top_offset + rows {
+                docu.highlight(doc_row, shown);
+            }
+        }
+    }
+
+    /// points the windows showing a closed buffer at the current buffer instead
+    pub(super) fn forget_buffer(&mut self, number: usize) {
+        let (current, buffer, cursor) = (self.windows.current, self.buffer_number, self.cursor());
+        let (top_offset, left_offset) = (self.top_offset, self.left_offset);
+        // window ids only tell windows apart within a tab, the current window is one of this tab's
+        let this_tab = self.windows.iter_mut().filter(|w| w.id != current);
+        let other_tabs = self.tabs.iter_mut().flat_map(Windows::iter_mut);
+        for window in this_tab.chain(other_tabs) {
+            if window.buffer == number {
+                window.buffer = buffer;
+                window.cursor = cursor;
+                window.top_offset = top_offset;
+                window.left_offset = left_offset;
+            }
+        }
+    }
+
+    fn move_to_window(&mut self, direction: Direction) {
+        if let Some(id) = self.windows.neighbor(direction) {
+            self.goto_window(id);
+        }
+    }
+
+    fn resize_window(&mut self, vertical: bool, amount: u16, grow: bool) {
+        self.windows.resize(vertical, amount, grow);
+        self.arrange_windows();
+    }
+
+    /// remembers where the current window is, its cursor and offsets live in the editor while it's current
+    pub(super) fn store_window(&mut self) {
+        let (buffer, cursor) = (self.buffer_number, self.cursor());
+        let (top_offset, left_offset) = (self.top_offset, self.left_offset);
+        let window = self.windows.current_mut();
+        window.buffer = buffer;
+        window.cursor = cursor;
+        window.top_offset = top_offset;
+        window.left_offset = left_offset;
+    }
+
+    pub(super) fn load_window(&mut self) {
+        let window = self.windows.current();
+        let (buffer, cursor) = (window.buffer, window.cursor);
+        let (top_offset, left_offset) = (window.top_offset, window.left_offset);
+        if buffer != self.buffer_number {
+            self.switch_to_buffer(buffer);
+        }
+        // edits made in another window may have removed the lines this one was on
+        self.set_cursor(self.docu.clamp(cursor));
+        self.top_offset = top_offset;
+        self.left_offset = left_offset;
+        self.update_offsets();
+    }
+}
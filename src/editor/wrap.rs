@@ -0,0 +1,129 @@
+//! `:set wrap`, the current window's side of soft wrapping: display lines for `gj` and `gk`
+//! and which rows the lines take up when scrolling.
+
+use super::Editor;
+use crate::document::Position;
+use crate::motion::Motion;
+use crate::screen::display_width;
+use crate::wrap::{ScreenRow, row_of, row_starts, screen_rows};
+use unicode_segmentation::UnicodeSegmentation;
+
+impl Editor {
+    /// `gj` and `gk`, moving by the rows on screen instead of the lines of the document
+    pub(super) fn move_display_line(&mut self, down: bool, count: usize) {
+        let Some(width) = self.wrap_width() else {
+            // without wrapping every line is a single row
+            let motion = if down { Motion::Down } else { Motion::Up };
+            self.move_cursor(motion, count);
+            return;
+        };
+        let mut line = self.cursor_y;
+        let text = self.docu.line(line);
+        let mut starts = row_starts(&text, width);
+        let mut row = row_of(&starts, self.cursor_x);
+        // how far across its row the cursor is
+        let x: usize = text
+            .graphemes(true)
+            .take(self.cursor_x)
+            .skip(starts[row])
+            .map(display_width)
+            .sum();
+        for _ in 0..count {
+            if down && row + 1 < starts.len() {
+                row += 1;
+            } else if down && line + 1 < self.docu.n_lines() {
+                line += 1;
+                starts = row_starts(&self.docu.line(line), width);
+                row = 0;
+            } else if !down && row > 0 {
+                row -= 1;
+            } else if !down && line > 0 {
+                line -= 1;
+                starts = row_starts(&self.docu.line(line), width);
+                row = starts.len() - 1;
+            } else {
+                break;
+            }
+        }
+        // the cursor keeps its place across the screen, or goes to the end of a row that's too short
+        let end = starts
+            .get(row + 1)
+            .map_or_else(|| self.docu.line_len(line), |next| next - 1);
+        let mut col = starts[row];
+        let mut width_before = 0;
+        for grapheme in self.docu.line(line).graphemes(true).skip(col) {
+            if col >= end || width_before + display_width(grapheme) > x {
+                break;
+            }
+            width_before += display_width(grapheme);
+            col += 1;
+        }
+        self.set_cursor(Position::new(line, col));
+        self.update_offsets();
+    }
+
+    /// the columns the current window has for text, next to the line numbers
+    pub(super) fn text_width(&self) -> usize {
+        let line_number_width = self.docu.n_lines().to_string().len() + 3;
+        let (_, width) = self.window_size();
+        width.saturating_sub(line_number_width)
+    }
+
+    /// how wide lines get before wrapping, none when they don't wrap
+    pub(super) fn wrap_width(&self) -> Option<usize> {
+        self.wrap.then(|| self.text_width())
+    }
+
+    /// what the rows of the current window show
+    pub(super) fn screen_rows(&self) -> Vec<ScreenRow> {
+        let (height, _) = self.window_size();
+        screen_rows(
+            &self.docu,
+            self.top_offset,
+            self.left_offset,
+            self.wrap_width(),
+            height,
+        )
+    }
+
+    /// with wrapping `left_offset` is where the top line starts on screen, so a line taller than the window
+    /// can be scrolled through to the cursor's row
+    pub(super) fn update_top_row(&mut self) {
+        let Some(width) = self.wrap_width() else {
+            return;
+        };
+        if self.cursor_y != self.top_offset {
+            self.left_offset = 0;
+            return;
+        }
+        let (height, _) = self.window_size();
+        let starts = row_starts(&self.docu.line(self.cursor_y), width);
+        let cursor_row = row_of(&starts, self.cursor_x);
+        // no further down than needed to fill the window, and never below the cursor's row or too far above it
+        let top_row = row_of(&starts, self.left_offset)
+            .min(starts.len().saturating_sub(height))
+            .min(cursor_row)
+            .max((cursor_row + 1).saturating_sub(height));
+        self.left_offset = starts[top_row];
+    }
+
+    /// how many rows of the current window `line` takes up
+    pub(super) fn line_rows(&self, line: usize) -> usize {
+        self.wrap_width()
+            .map_or(1, |width| row_starts(&self.docu.line(line), width).len())
+    }
+
+    /// the rows from the top of the current window down to the cursor's row
+    pub(super) fn rows_above_cursor(&self) -> usize {
+        let rows: usize = (self.top_offset..self.cursor_y)
+            .map(|line| self.line_rows(line))
+            .sum();
+        let cursor_row = self.wrap_width().map_or(0, |width| {
+            row_of(
+                &row_starts(&self.docu.line(self.cursor_y), width),
+                self.cursor_x,
+            )
+        });
+        rows + cursor_row
+    }
+}
@@ -0,0 +1,328 @@
+//! Remembers how a file was laid out on disk so saving it doesn't rewrite every line.
+//! The document itself always works with bare '\n' line breaks in a utf-8 string, this is applied on load and save.
+
+use encoding_rs::WINDOWS_1252;
+use std::fmt;
+use std::io::{Error, ErrorKind};
+
+const BOM: char = '\u{feff}';
+// how much of a file is looked at when deciding whether it's binary
+const BINARY_SNIFF_LEN: usize = 8000;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Windows1252, // also covers latin-1, anything that isn't valid utf-8 ends up here
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct FileFormat {
+    pub encoding: Encoding,
+    pub line_ending: LineEnding,
+    pub trailing_newline: bool,
+    pub bom: bool,
+    pub mixed_line_endings: bool, // the file had both kinds, saving it writes every line break as `line_ending`
+}
+
+impl Encoding {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(Encoding::Utf8),
+            "utf-16le" | "utf-16" | "ucs-2le" => Some(Encoding::Utf16Le),
+            "utf-16be" | "ucs-2" => Some(Encoding::Utf16Be),
+            "windows-1252" | "cp1252" | "latin1" | "latin-1" | "iso-8859-1" => {
+                Some(Encoding::Windows1252)
+            }
+            _ => None,
+        }
+    }
+
+    /// utf-16 is only detected by its BOM, so it always has to be written with one
+    pub fn needs_bom(self) -> bool {
+        matches!(self, Encoding::Utf16Le | Encoding::Utf16Be)
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Encoding::Utf8 => write!(f, "utf-8"),
+            Encoding::Utf16Le => write!(f, "utf-16le"),
+            Encoding::Utf16Be => write!(f, "utf-16be"),
+            Encoding::Windows1252 => write!(f, "windows-1252"),
+        }
+    }
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+
+    pub fn toggled(self) -> Self {
+        match self {
+            LineEnding::Lf => LineEnding::Crlf,
+            LineEnding::Crlf => LineEnding::Lf,
+        }
+    }
+}
+
+impl fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LineEnding::Lf => write!(f, "LF"),
+            LineEnding::Crlf => write!(f, "CRLF"),
+        }
+    }
+}
+
+impl FileFormat {
+    /// detects the encoding and layout of `bytes` and returns them along with the normalized text
+    /// mixed line endings are all normalized, the style of the first line break wins and the status bar says they were mixed
+    pub fn decode(bytes: &[u8]) -> (FileFormat, String) {
+        let (encoding, raw) = match bytes {
+            // utf-16 is only recognized by its BOM, which is decoded along with the text and stripped below
+            [0xff, 0xfe, ..] => (Encoding::Utf16Le, decode_utf16(bytes, u16::from_le_bytes)),
+            [0xfe, 0xff, ..] => (Encoding::Utf16Be, decode_utf16(bytes, u16::from_be_bytes)),
+            _ => {
+                if let Ok(text) = std::str::from_utf8(bytes) {
+                    (Encoding::Utf8, text.to_string())
+                } else {
+                    let (text, _) = WINDOWS_1252.decode_without_bom_handling(bytes);
+                    (Encoding::Windows1252, text.into_owned())
+                }
+            }
+        };
+
+        let bom = raw.starts_with(BOM);
+        let raw = raw.strip_prefix(BOM).unwrap_or(&raw);
+        let line_ending = match raw.find('\n') {
+            Some(idx) if raw[..idx].ends_with('\r') => LineEnding::Crlf,
+            _ => LineEnding::Lf,
+        };
+        let crlf_breaks = raw.matches("\r\n").count();
+        let mixed_line_endings = crlf_breaks > 0 && crlf_breaks < raw.matches('\n').count();
+        let mut text = raw.replace("\r\n", "\n");
+        let trailing_newline = text.ends_with('\n');
+        if trailing_newline {
+            text.pop();
+        }
+        let format = FileFormat {
+            encoding,
+            line_ending,
+            trailing_newline,
+            bom,
+            mixed_line_endings,
+        };
+        (format, text)
+    }
+
+    /// turns normalized text back into the bytes that should be written to disk
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, Error> {
+        let mut out = String::with_capacity(text.len() + 4);
+        if self.bom {
+            out.push(BOM);
+        }
+        match self.line_ending {
+            LineEnding::Lf => out.push_str(text),
+            LineEnding::Crlf => out.push_str(&text.replace('\n', "\r\n")),
+        }
+        if self.trailing_newline {
+            out.push_str(self.line_ending.as_str());
+        }
+
+        match self.encoding {
+            Encoding::Utf8 => Ok(out.into_bytes()),
+            Encoding::Utf16Le => Ok(out.encode_utf16().flat_map(u16::to_le_bytes).collect()),
+            Encoding::Utf16Be => Ok(out.encode_utf16().flat_map(u16::to_be_bytes).collect()),
+            Encoding::Windows1252 => {
+                let (bytes, _, had_errors) = WINDOWS_1252.encode(&out);
+                if had_errors {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        "text contains characters that can't be written as windows-1252",
+                    ));
+                }
+                Ok(bytes.into_owned())
+            }
+        }
+    }
+
+    /// a file is treated as binary if it has NUL bytes near the start, which no text encoding we read produces
+    /// (utf-16 has plenty of them, but we only read utf-16 with a BOM)
+    pub fn looks_binary(bytes: &[u8]) -> bool {
+        if bytes.starts_with(&[0xff, 0xfe]) || bytes.starts_with(&[0xfe, 0xff]) {
+            return false;
+        }
+        bytes.iter().take(BINARY_SNIFF_LEN).any(|&byte| byte == 0)
+    }
+
+    /// utf-16 with a byte left over at the end or unpaired surrogates decodes with replacement characters,
+    /// so writing the text back wouldn't give the same bytes
+    pub fn decodes_lossily(bytes: &[u8]) -> bool {
+        let to_unit = match bytes {
+            [0xff, 0xfe, ..] => u16::from_le_bytes,
+            [0xfe, 0xff, ..] => u16::from_be_bytes,
+            _ => return false,
+        };
+        !bytes.len().is_multiple_of(2)
+            || char::decode_utf16(utf16_units(bytes, to_unit)).any(|c| c.is_err())
+    }
+}
+
+/// the format new files are written in
+impl Default for FileFormat {
+    fn default() -> Self {
+        FileFormat {
+            encoding: Encoding::Utf8,
+            line_ending: LineEnding::Lf,
+            trailing_newline: true,
+            bom: false,
+            mixed_line_endings: false,
+        }
+    }
+}
+
+fn decode_utf16(bytes: &[u8], to_unit: fn([u8; 2]) -> u16) -> String {
+    char::decode_utf16(utf16_units(bytes, to_unit))
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+fn utf16_units(bytes: &[u8], to_unit: fn([u8; 2]) -> u16) -> impl Iterator<Item = u16> + '_ {
+    bytes
+        .chunks_exact(2)
+        .map(move |pair| to_unit([pair[0], pair[1]]))
+}
+
+impl fmt::Display for FileFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.encoding != Encoding::Utf8 {
+            write!(f, "{} ", self.encoding)?;
+        }
+        write!(f, "{}", self.line_ending)?;
+        if self.mixed_line_endings {
+            write!(f, " (mixed)")?;
+        }
+        if !self.trailing_newline {
+            write!(f, " noeol")?;
+        }
+        if self.bom && self.encoding == Encoding::Utf8 {
+            write!(f, " BOM")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(bytes: &[u8]) -> Vec<u8> {
+        let (format, text) = FileFormat::decode(bytes);
+        format.encode(&text).unwrap()
+    }
+
+    #[test]
+    fn line_endings_final_newline_and_bom_round_trip() {
+        let files: [&[u8]; 8] = [
+            b"",
+            b"one\ntwo\n",
+            b"one\r\ntwo\r\n",
+            b"one\ntwo",
+            b"one\r\ntwo",
+            b"\n\n",
+            b"\xef\xbb\xbfone\r\n",
+            b"a lone\rcarriage return\n",
+        ];
+        for bytes in files {
+            assert_eq!(round_trip(bytes), bytes);
+        }
+    }
+
+    #[test]
+    fn crlf_is_normalized_and_remembered() {
+        let (format, text) = FileFormat::decode(b"\xef\xbb\xbfone\r\ntwo");
+        assert_eq!(text, "one\ntwo");
+        assert!(format.line_ending == LineEnding::Crlf);
+        assert!(!format.trailing_newline);
+        assert!(format.bom);
+        assert!(!format.mixed_line_endings);
+    }
+
+    #[test]
+    fn mixed_line_endings_take_the_first_and_are_reported() {
+        let (format, text) = FileFormat::decode(b"one\r\ntwo\nthree\r\n");
+        assert_eq!(text, "one\ntwo\nthree");
+        assert!(format.line_ending == LineEnding::Crlf);
+        assert!(format.mixed_line_endings);
+        assert_eq!(format.encode(&text).unwrap(), b"one\r\ntwo\r\nthree\r\n");
+
+        let (format, _) = FileFormat::decode(b"one\ntwo\r\n");
+        assert!(format.line_ending == LineEnding::Lf);
+        assert!(format.mixed_line_endings);
+    }
+
+    #[test]
+    fn encodings_round_trip() {
+        let files: [&[u8]; 4] = [
+            "ünïcödé 日本 🎉\n".as_bytes(),
+            b"\xff\xfeo\x00k\x00 \x00=\xd8\x89\xdf\r\x00\n\x00",
+            b"\xfe\xff\x00o\x00k\x00 \xd8=\xdf\x89\x00\n",
+            b"caf\xe9 \x80\n",
+        ];
+        for bytes in files {
+            assert!(!FileFormat::decodes_lossily(bytes));
+            assert_eq!(round_trip(bytes), bytes);
+        }
+    }
+
+    #[test]
+    fn encodings_are_detected() {
+        let (format, text) = FileFormat::decode(b"\xff\xfeo\x00k\x00");
+        assert!(format.encoding == Encoding::Utf16Le && format.bom);
+        assert_eq!(text, "ok");
+        let (format, text) = FileFormat::decode(b"caf\xe9\n");
+        assert!(format.encoding == Encoding::Windows1252);
+        assert_eq!(text, "café");
+    }
+
+    #[test]
+    fn characters_windows_1252_lacks_are_refused() {
+        let format = FileFormat {
+            encoding: Encoding::Windows1252,
+            ..FileFormat::default()
+        };
+        assert!(format.encode("café").is_ok());
+        assert!(format.encode("日本").is_err());
+    }
+
+    #[test]
+    fn utf16_that_loses_bytes_is_detected() {
+        // a byte left over at the end
+        assert!(FileFormat::decodes_lossily(b"\xff\xfeo\x00k"));
+        // a high surrogate without the low one after it
+        assert!(FileFormat::decodes_lossily(b"\xff\xfeo\x00=\xd8k\x00"));
+        assert!(FileFormat::decodes_lossily(b"\xfe\xff\xdc\x00"));
+        // only utf-16 can lose anything, the other encodings take any bytes
+        assert!(!FileFormat::decodes_lossily(b"\xe9\xff\x00"));
+    }
+
+    #[test]
+    fn nul_bytes_mean_binary_except_in_utf16() {
+        assert!(FileFormat::looks_binary(b"ELF\x00\x01"));
+        assert!(!FileFormat::looks_binary(b"\xff\xfeo\x00k\x00"));
+        assert!(!FileFormat::looks_binary(b"text\n"));
+    }
+}
@@ -0,0 +1,409 @@
+//! Undo/redo bookkeeping for a document.
+//! Edits are recorded at the rope level (char indices) so replaying them doesn't depend on graphemes.
+
+use crate::document::Position;
+use std::env;
+use std::fmt::Write;
+use std::fs::{self, DirBuilder, OpenOptions};
+use std::io::{Error, ErrorKind, Write as _};
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+pub enum Edit {
+    Insert { at: usize, text: String },
+    Remove { at: usize, text: String },
+}
+
+/// one undo step, usually everything typed between entering and leaving insert mode
+pub struct UndoGroup {
+    pub edits: Vec<Edit>,
+    pub cursor_before: Position,
+    pub cursor_after: Position,
+}
+
+pub struct History {
+    undo_stack: Vec<UndoGroup>,
+    redo_stack: Vec<UndoGroup>,
+    open_group: Option<UndoGroup>,
+    saved: Option<usize>, // how many undo steps there were when the text matched the file, none once that can't come back
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            open_group: None,
+            saved: Some(0),
+        }
+    }
+}
+
+impl Edit {
+    pub fn inverse(&self) -> Edit {
+        match self {
+            Edit::Insert { at, text } => Edit::Remove {
+                at: *at,
+                text: text.clone(),
+            },
+            Edit::Remove { at, text } => Edit::Insert {
+                at: *at,
+                text: text.clone(),
+            },
+        }
+    }
+}
+
+impl History {
+    /// starts grouping edits together, does nothing if a group is already open
+    pub fn begin_group(&mut self, cursor: Position) {
+        if self.open_group.is_none() {
+            self.open_group = Some(UndoGroup {
+                edits: Vec::new(),
+                cursor_before: cursor,
+                cursor_after: cursor,
+            });
+        }
+    }
+
+    pub fn end_group(&mut self, cursor: Position) {
+        if let Some(mut group) = self.open_group.take() {
+            if group.edits.is_empty() {
+                return;
+            }
+            group.cursor_after = cursor;
+            self.undo_stack.push(group);
+        }
+    }
+
+    /// records an edit, edits made outside of a group become their own undo step
+    pub fn record(&mut self, edit: Edit, cursor: Position) {
+        // the saved text was somewhere among the undone steps, which are gone now
+        if self
+            .saved
+            .is_some_and(|saved| saved > self.undo_stack.len())
+        {
+            self.saved = None;
+        }
+        self.redo_stack.clear();
+        match &mut self.open_group {
+            Some(group) => group.edits.push(edit),
+            None => self.undo_stack.push(UndoGroup {
+                edits: vec![edit],
+                cursor_before: cursor,
+                cursor_after: cursor,
+            }),
+        }
+    }
+
+    pub fn pop_undo(&mut self) -> Option<UndoGroup> {
+        self.undo_stack.pop()
+    }
+
+    pub fn pop_redo(&mut self) -> Option<UndoGroup> {
+        self.redo_stack.pop()
+    }
+
+    pub fn push_undo(&mut self, group: UndoGroup) {
+        self.undo_stack.push(group);
+    }
+
+    pub fn push_redo(&mut self, group: UndoGroup) {
+        self.redo_stack.push(group);
+    }
+
+    /// remembers the current step as the one the file on disk has
+    pub fn mark_saved(&mut self) {
+        // edits in an open group become a step of their own only later, and more may join them
+        self.saved = if self.has_open_edits() {
+            None
+        } else {
+            Some(self.undo_stack.len())
+        };
+    }
+
+    /// whether undoing and redoing came back to the text the file on disk has
+    pub fn is_saved(&self) -> bool {
+        !self.has_open_edits() && self.saved == Some(self.undo_stack.len())
+    }
+
+    fn has_open_edits(&self) -> bool {
+        self.open_group
+            .as_ref()
+            .is_some_and(|group| !group.edits.is_empty())
+    }
+}
+
+// persistent history
+// the history of a file is kept in a sidecar file under the user's state directory, named after a hash of the file's
+// canonical path. it also stores a hash of the file's contents at save time so we can tell when the file was changed
+// behind our back, in which case the old history no longer lines up with the text and is thrown away
+const UNDO_FILE_HEADER: &str = "pascal-undo 1";
+const MAX_PERSISTED_GROUPS: usize = 1000; // per stack, the oldest undo steps are dropped from the sidecar beyond that
+
+impl History {
+    /// writes the undo and redo stacks to the sidecar file of `file_path`
+    pub fn persist(&self, file_path: &str, content: &[u8]) -> Result<(), Error> {
+        let canonical = fs::canonicalize(file_path)?;
+        let sidecar = sidecar_path(&canonical)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "no state directory"))?;
+        if let Some(dir) = sidecar.parent() {
+            let mut builder = DirBuilder::new();
+            builder.recursive(true);
+            #[cfg(unix)]
+            builder.mode(0o700);
+            builder.create(dir)?;
+        }
+        write_private(&sidecar, &self.serialize(&canonical, content))
+    }
+
+    /// loads the history saved for `file_path`, as long as the file still has the contents it was saved with
+    pub fn restore(file_path: &str, content: &[u8]) -> Option<History> {
+        let canonical = fs::canonicalize(file_path).ok()?;
+        let data = fs::read_to_string(sidecar_path(&canonical)?).ok()?;
+        Self::parse(&data, &canonical, content)
+    }
+
+    /// the contents of the sidecar file for the file at `canonical` holding `content`
+    fn serialize(&self, canonical: &Path, content: &[u8]) -> String {
+        let mut out = String::new();
+        out.push_str(UNDO_FILE_HEADER);
+        out.push('\n');
+        let _ = writeln!(out, "path {}", canonical.display());
+        let _ = writeln!(out, "hash {:016x}", fnv1a(content));
+        // the steps nearest to the saved text are at the ends of the stacks
+        let undo = &self.undo_stack[self.undo_stack.len().saturating_sub(MAX_PERSISTED_GROUPS)..];
+        let redo = &self.redo_stack[self.redo_stack.len().saturating_sub(MAX_PERSISTED_GROUPS)..];
+        write_stack(&mut out, "undo", undo);
+        write_stack(&mut out, "redo", redo);
+        out
+    }
+
+    /// reads back what `serialize` wrote, none if it was written for another file or other contents
+    fn parse(data: &str, canonical: &Path, content: &[u8]) -> Option<History> {
+        let mut reader = Reader { data, pos: 0 };
+
+        if reader.line()? != UNDO_FILE_HEADER
+            || reader.line()?.strip_prefix("path ")? != canonical.to_str()?
+            || u64::from_str_radix(reader.line()?.strip_prefix("hash ")?, 16).ok()?
+                != fnv1a(content)
+        {
+            return None;
+        }
+        let undo_stack = read_stack(&mut reader, "undo")?;
+        let redo_stack = read_stack(&mut reader, "redo")?;
+        // the file has the text as it was after the last undo step
+        let saved = Some(undo_stack.len());
+        Some(History {
+            undo_stack,
+            redo_stack,
+            open_group: None,
+            saved,
+        })
+    }
+}
+
+/// the sidecar holds deleted and old text of the file, so like vim's undo files only the user can read it
+fn write_private(path: &Path, content: &str) -> Result<(), Error> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path)?;
+    // the mode only applies to new files, sidecars written by older versions were readable by anyone
+    #[cfg(unix)]
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    file.write_all(content.as_bytes())
+}
+
+fn sidecar_path(canonical: &Path) -> Option<PathBuf> {
+    let state_dir = env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/state")))?;
+    let name = format!("{:016x}", fnv1a(canonical.as_os_str().as_encoded_bytes()));
+    Some(state_dir.join("pascal").join("undo").join(name))
+}
+
+/// FNV-1a, picked because it's tiny and its output never changes between builds unlike std's hashers
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+// a stack is written as "<name> <number of groups>" followed by the groups
+// each group is "group <number of edits> <line before> <col before> <line after> <col after>" followed by the edits
+// each edit is "<i|r> <char index> <byte length>" followed by the text itself and a line break, so the text can contain anything
+fn write_stack(out: &mut String, name: &str, stack: &[UndoGroup]) {
+    let _ = writeln!(out, "{name} {}", stack.len());
+    for group in stack {
+        let _ = writeln!(
+            out,
+            "group {} {} {} {} {}",
+            group.edits.len(),
+            group.cursor_before.line,
+            group.cursor_before.col,
+            group.cursor_after.line,
+            group.cursor_after.col
+        );
+        for edit in &group.edits {
+            let (kind, at, text) = match edit {
+                Edit::Insert { at, text } => ('i', at, text),
+                Edit::Remove { at, text } => ('r', at, text),
+            };
+            let _ = writeln!(out, "{kind} {at} {}", text.len());
+            out.push_str(text);
+            out.push('\n');
+        }
+    }
+}
+
+fn read_stack(reader: &mut Reader, name: &str) -> Option<Vec<UndoGroup>> {
+    let n_groups: usize = reader.line()?.strip_prefix(name)?.trim().parse().ok()?;
+    let mut stack = Vec::with_capacity(n_groups);
+    for _ in 0..n_groups {
+        let header = reader.line()?.strip_prefix("group ")?;
+        let fields = Reader::numbers(header)?;
+        let [n_edits, before_line, before_col, after_line, after_col] = fields[..] else {
+            return None;
+        };
+        let mut edits = Vec::with_capacity(n_edits);
+        for _ in 0..n_edits {
+            let header = reader.line()?;
+            let (kind, rest) = header.split_once(' ')?;
+            let [at, len] = Reader::numbers(rest)?[..] else {
+                return None;
+            };
+            let text = reader.take(len)?.to_string();
+            reader.line()?; // the line break after the text
+            edits.push(match kind {
+                "i" => Edit::Insert { at, text },
+                "r" => Edit::Remove { at, text },
+                _ => return None,
+            });
+        }
+        stack.push(UndoGroup {
+            edits,
+            cursor_before: Position::new(before_line, before_col),
+            cursor_after: Position::new(after_line, after_col),
+        });
+    }
+    Some(stack)
+}
+
+struct Reader<'a> {
+    data: &'a str,
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn line(&mut self) -> Option<&'a str> {
+        let rest = self.data.get(self.pos..)?;
+        let end = rest.find('\n')?;
+        self.pos += end + 1;
+        Some(&rest[..end])
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a str> {
+        let text = self.data.get(self.pos..self.pos + len)?;
+        self.pos += len;
+        Some(text)
+    }
+
+    fn numbers(fields: &str) -> Option<Vec<usize>> {
+        fields.split(' ').map(|n| n.parse().ok()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATH: &str = "/home/user/notes.txt";
+
+    fn insert(at: usize, text: &str) -> Edit {
+        Edit::Insert {
+            at,
+            text: text.to_string(),
+        }
+    }
+
+    /// an undo step typed in one go, another made of a single edit, and one that was undone
+    fn sample() -> History {
+        let mut history = History::default();
+        history.begin_group(Position::new(0, 0));
+        history.record(insert(0, "héllo\n"), Position::new(0, 0));
+        history.record(insert(6, "line\r\nwith 日本\n"), Position::new(1, 0));
+        history.end_group(Position::new(2, 0));
+        history.record(
+            Edit::Remove {
+                at: 3,
+                text: "l".to_string(),
+            },
+            Position::new(0, 3),
+        );
+        history.record(insert(0, "undone "), Position::new(0, 0));
+        let undone = history.pop_undo().unwrap();
+        history.push_redo(undone);
+        history
+    }
+
+    #[test]
+    fn sidecar_round_trips() {
+        let history = sample();
+        let data = history.serialize(Path::new(PATH), b"the text");
+        let restored = History::parse(&data, Path::new(PATH), b"the text").unwrap();
+        assert_eq!(restored.serialize(Path::new(PATH), b"the text"), data);
+        assert_eq!(restored.undo_stack.len(), 2);
+        assert_eq!(restored.redo_stack.len(), 1);
+        assert!(restored.is_saved());
+
+        let group = &restored.undo_stack[0];
+        assert_eq!(group.cursor_before, Position::new(0, 0));
+        assert_eq!(group.cursor_after, Position::new(2, 0));
+        assert!(
+            matches!(&group.edits[1], Edit::Insert { at: 6, text } if text == "line\r\nwith 日本\n")
+        );
+        assert!(
+            matches!(&restored.undo_stack[1].edits[0], Edit::Remove { at: 3, text } if text == "l")
+        );
+    }
+
+    #[test]
+    fn sidecar_of_other_contents_or_file_is_ignored() {
+        let data = sample().serialize(Path::new(PATH), b"the text");
+        assert!(History::parse(&data, Path::new(PATH), b"the text, changed").is_none());
+        assert!(History::parse(&data, Path::new("/home/user/other.txt"), b"the text").is_none());
+    }
+
+    #[test]
+    fn sidecar_of_another_version_or_cut_short_is_ignored() {
+        let data = sample().serialize(Path::new(PATH), b"the text");
+        let other_version = data.replacen(UNDO_FILE_HEADER, "pascal-undo 2", 1);
+        assert!(History::parse(&other_version, Path::new(PATH), b"the text").is_none());
+        let n_chars = data.chars().count();
+        for len in [0, n_chars / 2, n_chars - 1] {
+            let cut: String = data.chars().take(len).collect();
+            assert!(History::parse(&cut, Path::new(PATH), b"the text").is_none());
+        }
+    }
+
+    #[test]
+    fn sidecar_keeps_the_newest_steps() {
+        let mut history = History::default();
+        for at in 0..MAX_PERSISTED_GROUPS + 10 {
+            history.record(insert(at, "x"), Position::new(0, at));
+        }
+        let data = history.serialize(Path::new(PATH), b"");
+        let restored = History::parse(&data, Path::new(PATH), b"").unwrap();
+        assert_eq!(restored.undo_stack.len(), MAX_PERSISTED_GROUPS);
+        assert!(matches!(
+            restored.undo_stack[0].edits[0],
+            Edit::Insert { at: 10, .. }
+        ));
+    }
+}
@@ -1,27 +1,51 @@
+use crate::cli::{Cli, USAGE};
 use crate::editor::Editor;
 use std::env::{self};
+mod buffer;
+mod cli;
+mod clipboard;
+mod command;
 mod document;
 mod editor;
+mod file_format;
 mod highlighting;
+mod history;
 mod mode;
+mod motion;
+mod operator;
+mod register;
 mod render;
+mod screen;
+mod search;
+mod selection;
 mod statusbar;
+mod substitute;
 mod term;
+mod text_object;
+mod window;
+mod wrap;
 fn main() {
-    if let Some(file_name) = collect_args() {
-        match Editor::build(&file_name) {
-            Ok(mut editor) => editor.run(),
-            Err(e) => {
-                eprintln!("Error: Could not open file '{file_name}': {e}");
-                std::process::exit(1);
-            }
+    let args = match Cli::parse(env::args().skip(1)) {
+        Ok(Cli::Edit(args)) => args,
+        Ok(Cli::Help) => {
+            println!("{USAGE}");
+            return;
+        }
+        Ok(Cli::Version) => {
+            println!("pascal {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Err(e) => {
+            eprintln!("pascal: {e}");
+            eprintln!("Try 'pascal --help' for more information.");
+            std::process::exit(1);
+        }
+    };
+    match Editor::build(&args) {
+        Ok(mut editor) => editor.run(),
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
         }
-    } else {
-        eprintln!("Couldn't parse file location...");
-        std::process::exit(1);
     }
 }
-
-fn collect_args() -> Option<String> {
-    env::args().nth(1)
-}
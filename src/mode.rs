@@ -1,8 +1,15 @@
 use std::fmt;
 
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum Mode {
     Normal,
     Insert,
+    Command,
+    Search,
+    Confirm, // confirming each match of `:s///c`
+    Visual,
+    VisualLine,
+    VisualBlock,
 }
 
 impl fmt::Display for Mode {
@@ -10,6 +17,12 @@ impl fmt::Display for Mode {
         match self {
             Mode::Normal => write!(f, "NORMAL"),
             Mode::Insert => write!(f, "INSERT"),
+            Mode::Command => write!(f, "COMMAND"),
+            Mode::Search => write!(f, "SEARCH"),
+            Mode::Confirm => write!(f, "CONFIRM"),
+            Mode::Visual => write!(f, "VISUAL"),
+            Mode::VisualLine => write!(f, "V-LINE"),
+            Mode::VisualBlock => write!(f, "V-BLOCK"),
         }
     }
 }
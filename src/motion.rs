@@ -0,0 +1,92 @@
+//! Cursor movements, shared by plain movement, visual mode and operators like `d` and `y`.
+//! A motion only says where the cursor would end up, what happens with the text in between is up to the caller.
+
+use crate::document::{Document, Position};
+use crossterm::event::KeyCode;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordForward,
+    WordBackward,
+    LineStart,
+    LineEnd,
+    FirstLine,
+    LastLine,
+}
+
+impl Motion {
+    pub fn from_key(code: KeyCode) -> Option<Self> {
+        match code {
+            KeyCode::Char('h') | KeyCode::Left => Some(Motion::Left),
+            KeyCode::Char('l') | KeyCode::Right => Some(Motion::Right),
+            KeyCode::Char('k') | KeyCode::Up => Some(Motion::Up),
+            KeyCode::Char('j') | KeyCode::Down => Some(Motion::Down),
+            KeyCode::Char('w') => Some(Motion::WordForward),
+            KeyCode::Char('b') => Some(Motion::WordBackward),
+            KeyCode::Char('0') | KeyCode::Home => Some(Motion::LineStart),
+            KeyCode::Char('$') | KeyCode::End => Some(Motion::LineEnd),
+            KeyCode::Char('t') => Some(Motion::LastLine),
+            _ => None,
+        }
+    }
+
+    /// motions typed after `g`
+    pub fn from_g_key(code: KeyCode) -> Option<Self> {
+        match code {
+            KeyCode::Char('g') => Some(Motion::FirstLine),
+            _ => None,
+        }
+    }
+
+    /// linewise motions make operators act on whole lines, the others act on the characters
+    /// from the cursor up to but not including the target
+    pub fn is_linewise(self) -> bool {
+        matches!(
+            self,
+            Motion::Up | Motion::Down | Motion::FirstLine | Motion::LastLine
+        )
+    }
+
+    /// where the cursor ends up after moving `count` times from `from`
+    pub fn target(self, docu: &Document, from: Position, count: usize) -> Position {
+        let last_line = docu.n_lines() - 1;
+        // moving up or down keeps the column when the new line is long enough
+        let on_line = |line: usize| Position::new(line, from.col.min(docu.line_len(line)));
+        match self {
+            Motion::Left => Position::new(from.line, from.col.saturating_sub(count)),
+            Motion::Right => {
+                let col = from.col.saturating_add(count);
+                Position::new(from.line, col.min(docu.line_len(from.line)))
+            }
+            Motion::Up => on_line(from.line.saturating_sub(count)),
+            Motion::Down => on_line(from.line.saturating_add(count).min(last_line)),
+            Motion::WordForward => repeat(from, count, |pos| docu.next_word(pos)),
+            Motion::WordBackward => repeat(from, count, |pos| docu.prev_word(pos)),
+            Motion::LineStart => Position::new(from.line, 0),
+            Motion::LineEnd => {
+                // a count moves down to the end of a later line like in vim
+                let line = from.line.saturating_add(count - 1).min(last_line);
+                Position::new(line, docu.line_len(line))
+            }
+            Motion::FirstLine => Position::new(0, 0),
+            Motion::LastLine => Position::new(last_line, docu.line_len(last_line)),
+        }
+    }
+}
+
+/// takes `count` steps from `from`, stopping early at the start or end of the document where the steps get stuck
+fn repeat(from: Position, count: usize, step: impl Fn(Position) -> Position) -> Position {
+    let mut pos = from;
+    for _ in 0..count {
+        let next = step(pos);
+        if next == pos {
+            break;
+        }
+        pos = next;
+    }
+    pos
+}
@@ -0,0 +1,85 @@
+//! Operators like `d` and `y`, and the `[count] operator [count] motion` keys typed in normal mode.
+
+use crossterm::event::KeyCode;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Delete,
+    Change,
+    Yank,
+    Indent,
+    Dedent,
+}
+
+/// what has been typed of a command that isn't complete yet, e.g. `3d` waiting for a motion
+#[derive(Default)]
+pub struct PendingKeys {
+    count: Option<usize>,
+    pub operator: Option<Operator>,
+    operator_count: Option<usize>, // the count typed before the operator, `2` in `2d3w`
+    pub text_object: Option<bool>, // `i` or `a` was typed and the object is next, true for `a`
+    pub naming_register: bool,     // `"` was typed and the register name is next
+    pub window_command: bool,      // `Ctrl-w` was typed and what to do with the windows is next
+    pub g_prefix: bool, // `g` was typed and the rest of a command like `gg` or `gt` is next
+    register: Option<char>, // picked with `"x`, none for the unnamed register
+}
+
+impl Operator {
+    pub fn from_key(code: KeyCode) -> Option<Self> {
+        match code {
+            KeyCode::Char('d') => Some(Operator::Delete),
+            KeyCode::Char('c') => Some(Operator::Change),
+            KeyCode::Char('y') => Some(Operator::Yank),
+            KeyCode::Char('>') => Some(Operator::Indent),
+            KeyCode::Char('<') => Some(Operator::Dedent),
+            _ => None,
+        }
+    }
+}
+
+impl PendingKeys {
+    /// adds a digit to the count, a `0` that doesn't continue a count is the line start motion instead
+    pub fn push_digit(&mut self, c: char) -> bool {
+        let Some(digit) = c.to_digit(10) else {
+            return false;
+        };
+        if digit == 0 && self.count.is_none() {
+            return false;
+        }
+        let count = self.count.unwrap_or(0);
+        self.count = Some(count.saturating_mul(10).saturating_add(digit as usize));
+        true
+    }
+
+    /// the count typed so far, none if there isn't one
+    pub fn count(&self) -> Option<usize> {
+        self.count
+    }
+
+    pub fn set_register(&mut self, name: char) {
+        self.naming_register = false;
+        self.register = Some(name);
+    }
+
+    /// the register picked for the command, must be called before `take` which forgets it
+    pub fn register(&self) -> Option<char> {
+        self.register
+    }
+
+    pub fn set_operator(&mut self, operator: Operator) {
+        self.operator = Some(operator);
+        self.operator_count = self.count.take();
+    }
+
+    /// finishes the command, returning the operator if there is one and how many times to repeat the motion
+    /// counts before and after the operator multiply like in vim, `2d3w` deletes six words
+    pub fn take(&mut self) -> (Option<Operator>, usize) {
+        let count = self
+            .operator_count
+            .unwrap_or(1)
+            .saturating_mul(self.count.unwrap_or(1));
+        let operator = self.operator.take();
+        *self = PendingKeys::default();
+        (operator, count)
+    }
+}
@@ -0,0 +1,105 @@
+/// how the text was taken out of the document, which decides how it goes back in
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RegisterKind {
+    Chars,
+    Lines,
+    Block,
+}
+
+/// text that was yanked or deleted, kept so it can be put back
+#[derive(Clone)]
+pub struct Register {
+    pub text: String,
+    pub kind: RegisterKind,
+}
+
+impl Register {
+    /// adds to the register, anything involving whole lines makes the result whole lines like in vim
+    fn append(&mut self, other: &Register) {
+        if self.kind == RegisterKind::Lines || other.kind == RegisterKind::Lines {
+            if !self.text.ends_with('\n') {
+                self.text.push('\n');
+            }
+            self.text.push_str(&other.text);
+            if !self.text.ends_with('\n') {
+                self.text.push('\n');
+            }
+            self.kind = RegisterKind::Lines;
+        } else {
+            self.text.push_str(&other.text);
+        }
+    }
+
+    /// what vim says after yanking, nothing unless more than two lines were involved
+    pub fn yank_message(&self) -> Option<String> {
+        let n_lines = match self.kind {
+            RegisterKind::Lines => self.text.lines().count(),
+            RegisterKind::Chars | RegisterKind::Block => self.text.split('\n').count(),
+        };
+        if n_lines <= 2 {
+            return None;
+        }
+        Some(match self.kind {
+            RegisterKind::Block => format!("block of {n_lines} lines yanked"),
+            RegisterKind::Chars | RegisterKind::Lines => format!("{n_lines} lines yanked"),
+        })
+    }
+}
+
+/// registers are named like vim's: `"` for the last one, `a`-`z` picked by the user,
+/// `0` for the last yank, `1`-`9` for the last deletes, `_` for throwing text away and `+`/`*` for the system clipboard
+#[derive(Default)]
+pub struct Registers {
+    unnamed: Option<Register>, // a copy of whatever was stored last, what `p` uses without a register name
+    named: [Option<Register>; 26],
+    numbered: [Option<Register>; 10],
+    clipboard: Option<Register>, // the last text sent to the system clipboard, in case it can't be read back
+}
+
+impl Registers {
+    /// whether `name` can follow a `"`
+    pub fn is_valid(name: char) -> bool {
+        name.is_ascii_alphanumeric() || matches!(name, '"' | '_' | '+' | '*')
+    }
+
+    /// keeps text that was yanked or deleted, without a name yanks go to `0` and deletes push `1`-`9` down
+    pub fn store(&mut self, name: Option<char>, register: Register, deleted: bool) {
+        match name {
+            Some('_') => return,
+            Some(name @ 'a'..='z') => self.named[Self::index(name)] = Some(register.clone()),
+            Some('+' | '*') => self.clipboard = Some(register.clone()),
+            // an uppercase name appends to the lowercase register
+            Some(name @ 'A'..='Z') => {
+                let named = &mut self.named[Self::index(name.to_ascii_lowercase())];
+                match named {
+                    Some(existing) => existing.append(&register),
+                    None => *named = Some(register),
+                }
+                self.unnamed.clone_from(named);
+                return;
+            }
+            _ if deleted => {
+                self.numbered[1..].rotate_right(1);
+                self.numbered[1] = Some(register.clone());
+            }
+            _ => self.numbered[0] = Some(register.clone()),
+        }
+        self.unnamed = Some(register);
+    }
+
+    pub fn get(&self, name: Option<char>) -> Option<&Register> {
+        match name {
+            None | Some('"') => self.unnamed.as_ref(),
+            Some('+' | '*') => self.clipboard.as_ref(),
+            Some(digit @ '0'..='9') => self.numbered[digit as usize - '0' as usize].as_ref(),
+            Some(name) if name.is_ascii_alphabetic() => {
+                self.named[Self::index(name.to_ascii_lowercase())].as_ref()
+            }
+            Some(_) => None,
+        }
+    }
+
+    fn index(name: char) -> usize {
+        name as usize - 'a' as usize
+    }
+}
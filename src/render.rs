@@ -1,213 +1,408 @@
-use crate::editor::Editor;
+use crate::command::CommandLine;
+use crate::document::{Document, Position};
+use crate::editor::{Editor, display_name};
+use crate::mode::Mode;
+use crate::screen::{Screen, display_width};
+use crate::search::line_matches;
+use crate::selection::Selection;
+use crate::statusbar::StatusBar;
 use crate::term::Terminal;
+use crate::window::Rect;
+use crate::wrap::{ScreenRow, screen_rows};
+use crossterm::style::Color;
 use std::io::Error;
+use std::ops::Range;
 use unicode_segmentation::UnicodeSegmentation;
-use unicode_width::UnicodeWidthStr;
 
 pub struct Renderer<'a> {
-    editor: &'a mut Editor,
+    editor: &'a Editor,
+    screen: &'a mut Screen,
+}
+
+// every frame is drawn in full into the screen's back-buffer, which works out what changed since the last frame
+// and only sends that to the terminal, so moving the cursor costs a few bytes instead of a full repaint
+// syntax highlighting is cached per line in the document and only recalculated for lines that were edited
+/// a window as it gets drawn, the current window's cursor and offsets come from the editor
+struct View<'b> {
+    docu: &'b Document,
+    cursor: Position,
+    top_offset: usize,
+    left_offset: usize,
+    rect: Rect,
+    active: bool,
+    unsaved: bool,
 }
 
-// currently rendering is done every time the user moves the cursor or edits a line
-// optimization is possible by rendering only necessary lines which should improve performance on older systems
-// possible optimizations:
-// rerender only a single line when user edits it
-// rerender only the line and the ones below it when the user does a newline
-// rerender only the cursor when the user moves it
-// rerender the entire screen only when it requires scrolling or it has been resized
-// syntax highlighting is done every time a render operation is done, instead we should cache it and only recalculate when document changes
 impl<'a> Renderer<'a> {
-    /// This is called by Editor's render method
-    pub fn new(editor: &'a mut Editor) -> Self {
-        Renderer { editor }
+    /// This is called by Editor's render method, after the visible lines were highlighted
+    pub fn new(editor: &'a Editor, screen: &'a mut Screen) -> Self {
+        Renderer { editor, screen }
     }
 
-    /// Renders all the lines of the document and the cursor
+    /// Renders every window, the command line and the cursor
     pub fn render(&mut self) -> Result<(), Error> {
-        Terminal::clear()?;
-        Terminal::hide_cursor()?;
-        self.highlight_renderable_lines();
-        self.render_document_lines()?;
-        self.render_status_bar()?;
-        Terminal::show_cursor()?;
-        self.render_cursor()?;
-        Terminal::flush()?;
-        Ok(())
-    }
-
-    fn highlight_renderable_lines(&mut self) {
-        let height = self.editor.term.height;
-        let docu = &mut self.editor.docu;
-        for row in 0..height - 1 {
-            let doc_row = self.editor.top_offset + row; // for vertical scrolling
-            if doc_row < docu.n_lines {
-                let line = &docu.lines[doc_row as usize];
-                if docu.rich_lines[doc_row as usize].is_empty() {
-                    docu.rich_lines[doc_row as usize].recalc(
-                        &docu.highlighter,
-                        line,
-                        &docu.extension,
-                    );
-                }
-            }
+        self.screen
+            .begin(self.editor.term.width, self.editor.term.height);
+        for view in self.views() {
+            self.render_document_lines(&view);
+            self.render_status_bar(&view);
         }
+        self.render_separators();
+        self.render_tab_line();
+        self.render_command_line();
+        let cursor = self.cursor_position();
+        // the terminal shows the frame once it's complete instead of tearing halfway through a scroll or resize
+        let synchronized = self.editor.term.synchronized_output;
+        if synchronized {
+            Terminal::begin_synchronized_update()?;
+        }
+        self.screen.queue_changes(cursor)?;
+        if synchronized {
+            Terminal::end_synchronized_update()?;
+        }
+        Terminal::flush()
     }
 
-    /// re-renders a specific set of lines only
-    pub fn re_render_line(&self, from: u16, to: u16) -> Result<(), Error> {
-        Terminal::hide_cursor()?;
-        let max_row = self.editor.term.height.saturating_sub(2); // Exclude status bar and last line
-        for row in from..=to {
-            if row >= max_row {
-                break; // Don't render over status bar or below
-            }
-            Terminal::move_cursor(0, row)?;
-            Terminal::clear_current_line()?;
-            let doc_row = self.editor.top_offset + row;
-            if doc_row < self.editor.docu.n_lines {
-                self.render_content_line(doc_row)?;
-            } else {
-                self.render_empty_line()?;
-            }
-        }
-        Terminal::show_cursor()?;
-        Terminal::flush()?;
-        Ok(())
+    /// what every window shows, the current window last
+    fn views(&self) -> Vec<View<'a>> {
+        let editor = self.editor;
+        let mut views: Vec<View> = editor
+            .windows
+            .iter()
+            .filter(|window| window.id != editor.windows.current)
+            .map(|window| {
+                let (docu, unsaved) = editor.buffer_state(window.buffer);
+                View {
+                    docu,
+                    cursor: window.cursor,
+                    top_offset: window.top_offset,
+                    left_offset: window.left_offset,
+                    rect: window.rect,
+                    active: false,
+                    unsaved,
+                }
+            })
+            .collect();
+        views.push(self.current_view());
+        views
     }
 
-    fn render_document_lines(&self) -> Result<(), Error> {
-        let height = self.editor.term.height;
-        for row in 0..height - 1 {
-            Terminal::move_cursor(0, row)?;
-            let doc_row = self.editor.top_offset + row; // for vertical scrolling
+    fn current_view(&self) -> View<'a> {
+        let editor = self.editor;
+        View {
+            docu: &editor.docu,
+            cursor: self.editor.cursor(),
+            top_offset: self.editor.top_offset,
+            left_offset: self.editor.left_offset,
+            rect: self.editor.windows.current().rect,
+            active: true,
+            unsaved: self.editor.status_bar.has_unsaved_changes,
+        }
+    }
 
-            if doc_row < self.editor.docu.n_lines {
-                self.render_content_line(doc_row)?;
-            } else {
-                self.render_empty_line()?;
+    fn render_document_lines(&mut self, view: &View) {
+        let rows = self.screen_rows(view);
+        for row in 0..view.rect.height.saturating_sub(1) {
+            self.screen.move_to(view.rect.x, view.rect.y + row);
+            match rows.get(usize::from(row)) {
+                Some(screen_row) => self.render_content_line(view, screen_row),
+                None => self.render_empty_line(view),
             }
         }
-
-        Ok(())
     }
 
-    fn render_content_line(&self, doc_row: u16) -> Result<(), Error> {
-        self.render_line_number(doc_row)?;
-        self.render_line_content(doc_row)?;
+    /// what the rows of a window show, lines take several rows when they wrap
+    fn screen_rows(&self, view: &View) -> Vec<ScreenRow> {
+        let wrap_width = self.editor.wrap.then(|| Self::text_width(view));
+        screen_rows(
+            view.docu,
+            view.top_offset,
+            view.left_offset,
+            wrap_width,
+            usize::from(view.rect.height.saturating_sub(1)),
+        )
+    }
 
-        Ok(())
+    fn render_content_line(&mut self, view: &View, screen_row: &ScreenRow) {
+        // the rows a wrapped line continues on have no line number
+        if screen_row.cols.start == 0 || !self.editor.wrap {
+            self.render_line_number(view, screen_row.line);
+        } else {
+            self.render_continuation(view);
+        }
+        self.render_line_content(view, screen_row.line, screen_row.cols.clone());
     }
 
-    fn render_line_number(&self, row: u16) -> Result<(), Error> {
+    fn render_line_number(&mut self, view: &View, row: usize) {
         let line_number = row + 1;
         let line_number_str = format!(
             "{:>width$}",
             line_number,
-            width = self.get_line_number_width()
+            width = Self::get_line_number_width(view)
         );
         // render the line number
-        if row == self.editor.cursor_y {
-            Terminal::set_foreground_color(crossterm::style::Color::White)?;
+        if row == view.cursor.line {
+            self.screen.set_foreground(Color::White);
         } else {
-            Terminal::set_foreground_color(crossterm::style::Color::DarkGrey)?;
+            self.screen.set_foreground(Color::DarkGrey);
         }
-        Terminal::print(line_number_str)?;
+        self.screen.print(&line_number_str);
 
         // render the separator
-        Terminal::set_foreground_color(crossterm::style::Color::DarkGrey)?;
-        Terminal::print(" │ ")?;
-        Terminal::reset_color()?;
-        Ok(())
+        self.screen.set_foreground(Color::DarkGrey);
+        self.screen.print(" │ ");
+        self.screen.reset_colors();
     }
 
-    fn render_line_content(&self, doc_row: u16) -> Result<(), Error> {
-        let width = self.editor.term.width;
-        let rich_line = &self.editor.docu.rich_lines[doc_row as usize];
-        let available_width = width.saturating_sub((self.get_line_number_width() + 3) as u16);
+    fn render_continuation(&mut self, view: &View) {
+        let blank = " ".repeat(Self::get_line_number_width(view));
+        self.screen.print(&blank);
+        self.screen.set_foreground(Color::DarkGrey);
+        self.screen.print(" │ ");
+        self.screen.reset_colors();
+    }
+
+    /// draws the graphemes `cols` of a line, as many as fit
+    fn render_line_content(&mut self, view: &View, doc_row: usize, cols: Range<usize>) {
+        let rich_line = &view.docu.rich_lines[doc_row];
+        let available_width = Self::text_width(view) as u16;
 
         let highlighted_segments = &rich_line.line;
+        let search_matches = match self.editor.search.regex() {
+            Some(regex) => line_matches(&view.docu.line(doc_row), regex),
+            None => Vec::new(),
+        };
+        // the match a `:s///c` is asking about stands out from the other matches
+        let confirm_match = self
+            .editor
+            .substitution
+            .as_ref()
+            .filter(|_| view.active)
+            .and_then(|substitution| substitution.current_match(view.docu))
+            .filter(|(line, _)| *line == doc_row)
+            .map(|(_, cols)| cols);
+        let selection = self.editor.selection().filter(|_| view.active);
 
         let mut width_remaining = 0;
         let mut char_position = 0; // track position
         for segment in highlighted_segments {
             let segment_graphemes: Vec<&str> = segment.content.graphemes(true).collect();
             for grapheme in segment_graphemes {
-                if char_position < self.editor.left_offset as usize {
+                if char_position < cols.start {
                     char_position += 1;
                     continue;
                 }
-                let grapheme_width = grapheme.width() as u16;
+                if char_position >= cols.end {
+                    self.screen.reset_colors();
+                    return;
+                }
+                let grapheme_width = display_width(grapheme) as u16;
                 if width_remaining + grapheme_width > available_width {
-                    Terminal::reset_color()?;
-                    return Ok(());
+                    self.screen.reset_colors();
+                    return;
                 }
-                Self::apply_styling(&segment.style)?;
-                Terminal::print(grapheme)?;
+                self.apply_styling(&segment.style);
+                // search matches are drawn as a background so the syntax colors stay visible
+                if confirm_match
+                    .as_ref()
+                    .is_some_and(|m| m.contains(&char_position))
+                {
+                    self.screen.set_background(Color::DarkMagenta);
+                } else if selection.is_some_and(|sel| sel.contains(doc_row, char_position)) {
+                    self.screen.set_background(Color::DarkGrey);
+                } else if search_matches.iter().any(|m| m.contains(&char_position)) {
+                    self.screen.set_background(Color::DarkYellow);
+                } else {
+                    self.screen.set_background(Color::Reset);
+                }
+                self.screen.print(grapheme);
                 width_remaining += grapheme_width;
                 char_position += 1;
             }
         }
-        Terminal::reset_color()?;
-        Ok(())
+        // a selected line break shows as one selected cell so empty lines can be seen to be selected
+        let line_break_selected = match selection {
+            Some(Selection::Block { .. }) | None => false,
+            // the highlighting of a long line stops short of its end
+            Some(_) if !rich_line.complete => false,
+            Some(sel) => sel.contains(doc_row, char_position.max(cols.start)),
+        };
+        if line_break_selected && width_remaining < available_width {
+            self.screen.set_background(Color::DarkGrey);
+            self.screen.print(" ");
+        }
+        self.screen.reset_colors();
     }
 
-    fn apply_styling(style: &syntect::highlighting::Style) -> Result<(), Error> {
+    fn apply_styling(&mut self, style: &syntect::highlighting::Style) {
         let fg = style.foreground;
-        let foreground_color = crossterm::style::Color::Rgb {
+        let foreground_color = Color::Rgb {
             r: fg.r,
             g: fg.g,
             b: fg.b,
         };
-        Terminal::set_foreground_color(foreground_color)?;
-        Ok(())
-    }
-
-    fn render_empty_line(&self) -> Result<(), Error> {
-        let empty_line = format!("{:>width$}", "~", width = self.get_line_number_width());
-        Terminal::set_foreground_color(crossterm::style::Color::DarkGrey)?;
-        Terminal::print(&empty_line)?;
-        Terminal::reset_color()?;
-        Ok(())
-    }
-
-    fn get_line_number_width(&self) -> usize {
-        self.editor.docu.n_lines.to_string().len()
-    }
-
-    pub fn render_cursor(&self) -> Result<(), Error> {
-        let cursor_screen_y = (self.editor.cursor_y.saturating_sub(self.editor.top_offset))
-            .min(self.editor.term.height - 1);
-        let line = &self.editor.docu.lines[self.editor.cursor_y as usize];
-        let graphemes: Vec<&str> = line.graphemes(true).collect();
-        let line_number_width = (self.get_line_number_width() + 3) as u16;
-        let cursor_screen_x = line_number_width
-            + graphemes
-                .iter()
-                .skip(self.editor.left_offset as usize)
-                .take(self.editor.cursor_x.saturating_sub(self.editor.left_offset) as usize)
-                .map(|g| g.width() as u16)
+        self.screen.set_foreground(foreground_color);
+    }
+
+    fn render_empty_line(&mut self, view: &View) {
+        let empty_line = format!("{:>width$}", "~", width = Self::get_line_number_width(view));
+        self.screen.set_foreground(Color::DarkGrey);
+        self.screen.print(&empty_line);
+        self.screen.reset_colors();
+    }
+
+    fn get_line_number_width(view: &View) -> usize {
+        view.docu.n_lines().to_string().len()
+    }
+
+    /// the columns of a window next to the line numbers
+    fn text_width(view: &View) -> usize {
+        usize::from(view.rect.width).saturating_sub(Self::get_line_number_width(view) + 3)
+    }
+
+    /// where the terminal cursor goes, on the prompt while typing one and in the current window otherwise
+    fn cursor_position(&self) -> (u16, u16) {
+        if let Some((_, command_line)) = self.prompt() {
+            let cursor_screen_x = 1 + command_line
+                .input
+                .graphemes(true)
+                .take(command_line.cursor)
+                .map(|g| display_width(g) as u16)
+                .sum::<u16>();
+            return (cursor_screen_x, self.editor.term.height - 1);
+        }
+        let view = self.current_view();
+        // the row of the cursor's line it's on, the window's last row if it doesn't fit on screen
+        let cursor_row = self
+            .screen_rows(&view)
+            .into_iter()
+            .enumerate()
+            .rfind(|(_, row)| row.line == view.cursor.line && row.cols.start <= view.cursor.col);
+        let (row, start) = cursor_row.map_or_else(
+            || (view.rect.height.saturating_sub(2), view.left_offset),
+            |(idx, row)| (idx as u16, row.cols.start),
+        );
+        let cursor_screen_y = view.rect.y + row;
+        let line_number_width = (Self::get_line_number_width(&view) + 3) as u16;
+        let cursor_screen_x = view.rect.x
+            + line_number_width
+            + view
+                .docu
+                .graphemes(view.cursor.line)
+                .take(view.cursor.col)
+                .skip(start)
+                .map(|g| display_width(&g) as u16)
                 .sum::<u16>();
-        Terminal::move_cursor(cursor_screen_x, cursor_screen_y)?;
-        Ok(())
+        // at the end of a full row the cursor stays on the row's last cell
+        let last_column = view.rect.x + view.rect.width.saturating_sub(1);
+        (cursor_screen_x.min(last_column), cursor_screen_y)
     }
 
-    pub fn render_status_bar(&self) -> Result<(), Error> {
-        let status_bar = self.editor.status_bar.format(
-            self.editor.term.width,
-            self.editor.status_bar.has_unsaved_changes,
-            self.editor.cursor_y,
-            self.editor.cursor_x,
-            self.editor.docu.n_lines,
+    /// the last row of a window, the current window's stands out and has the mode and messages
+    fn render_status_bar(&mut self, view: &View) {
+        let inactive;
+        let status = if view.active {
+            &self.editor.status_bar
+        } else {
+            let mut status_bar = StatusBar::new(
+                display_name(&view.docu.file_name),
+                Mode::Normal,
+                view.unsaved,
+            );
+            status_bar.read_only = view.docu.read_only;
+            status_bar.is_new = view.docu.is_new;
+            inactive = status_bar;
+            &inactive
+        };
+        let status_bar = status.format(
+            view.rect.width,
+            view.cursor.line,
+            view.cursor.col,
+            view.docu.n_lines(),
+            &view.docu.format,
+            view.active,
+        );
+        // the status line fills the width of the window and no more
+        let width = usize::from(view.rect.width);
+        let status_bar: String = format!("{status_bar:<width$}")
+            .chars()
+            .take(width)
+            .collect();
+
+        self.screen.move_to(
+            view.rect.x,
+            view.rect.y + view.rect.height.saturating_sub(1),
         );
+        let background = if view.active {
+            Color::DarkBlue
+        } else {
+            Color::DarkGrey
+        };
+        self.screen.set_background(background);
+        self.screen.set_foreground(Color::Black);
+        self.screen.print(&status_bar);
+        self.screen.reset_colors();
+    }
 
-        // print status line at the bottom
-        Terminal::move_cursor(0, self.editor.term.height - 2)?;
-        Terminal::set_background_color(crossterm::style::Color::DarkBlue)?;
-        Terminal::set_foreground_color(crossterm::style::Color::Black)?;
-        Terminal::clear_current_line()?;
-        Terminal::print(&status_bar)?;
-        Terminal::reset_color()?;
+    /// the top row listing the tabs, the current one stands out
+    fn render_tab_line(&mut self) {
+        if !self.editor.has_tab_line() {
+            return;
+        }
+        let current = self.editor.current_tab();
+        let mut width_remaining = usize::from(self.editor.term.width);
+        self.screen.move_to(0, 0);
+        for (idx, label) in self.editor.tab_labels().into_iter().enumerate() {
+            let label: String = label.chars().take(width_remaining).collect();
+            width_remaining -= label.chars().count();
+            if idx == current {
+                self.screen.set_background(Color::DarkBlue);
+            } else {
+                self.screen.set_background(Color::DarkGrey);
+            }
+            self.screen.set_foreground(Color::Black);
+            self.screen.print(&label);
+        }
+        self.screen.set_background(Color::DarkGrey);
+        self.screen.print(&" ".repeat(width_remaining));
+        self.screen.reset_colors();
+    }
+
+    /// the columns between windows side by side
+    fn render_separators(&mut self) {
+        self.screen.set_foreground(Color::DarkGrey);
+        for separator in &self.editor.windows.separators {
+            for row in separator.y..separator.y + separator.height {
+                self.screen.move_to(separator.x, row);
+                self.screen.print("│");
+            }
+        }
+        self.screen.reset_colors();
+    }
 
-        Ok(())
+    /// the row below the status bar, where commands and searches are typed
+    fn render_command_line(&mut self) {
+        if let Some(substitution) = &self.editor.substitution {
+            self.screen.move_to(0, self.editor.term.height - 1);
+            self.screen.print(&substitution.prompt);
+            return;
+        }
+        let Some((prompt, command_line)) = self.prompt() else {
+            return;
+        };
+        self.screen.move_to(0, self.editor.term.height - 1);
+        self.screen
+            .print(&format!("{prompt}{}", command_line.input));
+    }
+
+    /// the prompt being typed into on the bottom row, if any
+    fn prompt(&self) -> Option<(char, &'a CommandLine)> {
+        let editor = self.editor;
+        match editor.mode {
+            Mode::Command => Some((':', &editor.command_line)),
+            Mode::Search if editor.search.forward => Some(('/', &editor.search_line)),
+            Mode::Search => Some(('?', &editor.search_line)),
+            _ => None,
+        }
     }
 }
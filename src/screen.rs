@@ -0,0 +1,196 @@
+//! A back-buffer of styled cells covering the terminal. Every frame is drawn into it in full,
+//! `queue_changes` compares it with the frame before and only sends the cells that changed.
+
+use crate::term::Terminal;
+use crossterm::style::Color;
+use std::io::Error;
+use std::mem;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+const TAB_WIDTH: usize = 4; // the spaces a tab shows as, as many as the Tab key inserts
+
+#[derive(Clone, PartialEq, Eq)]
+struct Cell {
+    symbol: String, // a grapheme, empty for the second column of a wide character
+    fg: Color,
+    bg: Color,
+}
+
+pub struct Screen {
+    width: u16,
+    height: u16,
+    cells: Vec<Cell>,
+    previous: Vec<Cell>, // what the terminal shows, empty when it has to be painted from scratch
+    x: u16,              // where the next `print` goes and in which colors
+    y: u16,
+    fg: Color,
+    bg: Color,
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Screen {
+            width: 0,
+            height: 0,
+            cells: Vec::new(),
+            previous: Vec::new(),
+            x: 0,
+            y: 0,
+            fg: Color::Reset,
+            bg: Color::Reset,
+        }
+    }
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Cell {
+            symbol: " ".to_string(),
+            fg: Color::Reset,
+            bg: Color::Reset,
+        }
+    }
+}
+
+impl Screen {
+    /// starts a blank frame, a new size means the terminal gets painted from scratch
+    pub fn begin(&mut self, width: u16, height: u16) {
+        if (width, height) != (self.width, self.height) {
+            self.width = width;
+            self.height = height;
+            self.previous.clear();
+        }
+        self.cells.clear();
+        self.cells
+            .resize(usize::from(width) * usize::from(height), Cell::default());
+        self.move_to(0, 0);
+        self.reset_colors();
+    }
+
+    pub fn move_to(&mut self, x: u16, y: u16) {
+        self.x = x;
+        self.y = y;
+    }
+
+    pub fn set_foreground(&mut self, color: Color) {
+        self.fg = color;
+    }
+
+    pub fn set_background(&mut self, color: Color) {
+        self.bg = color;
+    }
+
+    pub fn reset_colors(&mut self) {
+        self.fg = Color::Reset;
+        self.bg = Color::Reset;
+    }
+
+    /// writes `text` from the current position on, whatever doesn't fit on the row is cut off
+    pub fn print(&mut self, text: &str) {
+        for grapheme in text.graphemes(true) {
+            // the terminal would act on a tab or another control character instead of drawing one cell for it
+            if grapheme == "\t" {
+                for _ in 0..TAB_WIDTH {
+                    self.put(" ");
+                }
+            } else if let Some(control) = grapheme.chars().find(|c| c.is_control()) {
+                self.put(&control_picture(control).to_string());
+            } else {
+                self.put(grapheme);
+            }
+        }
+    }
+
+    /// fills the cells under a grapheme that the terminal draws as it is
+    fn put(&mut self, grapheme: &str) {
+        let width = grapheme.width() as u16;
+        if self.y >= self.height || width == 0 {
+            return;
+        }
+        if self.x + width > self.width {
+            self.x = self.width;
+            return;
+        }
+        let idx = self.idx(self.x, self.y);
+        self.cells[idx] = Cell {
+            symbol: grapheme.to_string(),
+            fg: self.fg,
+            bg: self.bg,
+        };
+        for column in 1..width {
+            self.cells[idx + usize::from(column)] = Cell {
+                symbol: String::new(),
+                fg: self.fg,
+                bg: self.bg,
+            };
+        }
+        self.x += width;
+    }
+
+    /// queues the cells that changed since the last frame for the terminal and puts the cursor at `cursor`,
+    /// nothing is sent until stdout is flushed
+    pub fn queue_changes(&mut self, cursor: (u16, u16)) -> Result<(), Error> {
+        Terminal::hide_cursor()?;
+        let repaint = self.previous.len() != self.cells.len();
+        if repaint {
+            Terminal::clear()?;
+        }
+        let mut colors = None;
+        let mut position = None; // where the terminal's cursor is after the last cell sent
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let idx = self.idx(x, y);
+                let cell = &self.cells[idx];
+                let unchanged = if repaint {
+                    *cell == Cell::default()
+                } else {
+                    self.previous[idx] == *cell
+                };
+                // the second half of a wide character goes out with the first
+                if unchanged || cell.symbol.is_empty() {
+                    continue;
+                }
+                if position != Some((x, y)) {
+                    Terminal::move_cursor(x, y)?;
+                }
+                if colors != Some((cell.fg, cell.bg)) {
+                    Terminal::set_foreground_color(cell.fg)?;
+                    Terminal::set_background_color(cell.bg)?;
+                    colors = Some((cell.fg, cell.bg));
+                }
+                Terminal::print(&cell.symbol)?;
+                position = Some((x + cell.symbol.width() as u16, y));
+            }
+        }
+        Terminal::reset_color()?;
+        Terminal::move_cursor(cursor.0, cursor.1)?;
+        Terminal::show_cursor()?;
+        mem::swap(&mut self.previous, &mut self.cells);
+        Ok(())
+    }
+
+    fn idx(&self, x: u16, y: u16) -> usize {
+        usize::from(y) * usize::from(self.width) + usize::from(x)
+    }
+}
+
+/// how many cells a grapheme of the document takes up on screen, the same as `Screen::print` gives it
+pub fn display_width(grapheme: &str) -> usize {
+    if grapheme == "\t" {
+        TAB_WIDTH
+    } else if grapheme.chars().any(char::is_control) {
+        1
+    } else {
+        grapheme.width()
+    }
+}
+
+/// the visible stand-in for a control character, like `␀` for a null byte
+fn control_picture(control: char) -> char {
+    match control {
+        '\0'..='\x1f' => char::from_u32(0x2400 + u32::from(control)).unwrap_or('\u{fffd}'),
+        '\x7f' => '\u{2421}',
+        _ => '\u{fffd}',
+    }
+}
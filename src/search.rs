@@ -0,0 +1,96 @@
+//! Finding text in a document, used by `/`, `?`, `n`, `N` and `:s`.
+//! Patterns are regular expressions matched against one line at a time, positions are in graphemes like the cursor.
+
+use crate::document::{Document, Position};
+use regex::Regex;
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Default)]
+pub struct Search {
+    pattern: Option<String>,
+    regex: Option<Regex>, // compiled `pattern`, highlighted on screen while set
+    pub forward: bool,
+    pub origin: Position, // where the cursor was when the search prompt was opened
+    previous_pattern: Option<String>,
+}
+
+impl Search {
+    /// remembers where the search started so it can be cancelled
+    pub fn start(&mut self, forward: bool, origin: Position) {
+        self.forward = forward;
+        self.origin = origin;
+        self.previous_pattern = self.pattern.clone();
+    }
+
+    /// brings back the pattern that was active before the search prompt was opened
+    pub fn cancel(&mut self) {
+        let previous = self.previous_pattern.take();
+        self.set_pattern(previous);
+    }
+
+    pub fn pattern(&self) -> Option<&str> {
+        self.pattern.as_deref()
+    }
+
+    pub fn regex(&self) -> Option<&Regex> {
+        self.regex.as_ref()
+    }
+
+    /// a pattern that isn't a valid regex (yet, while it's being typed) simply matches nothing
+    pub fn set_pattern(&mut self, pattern: Option<String>) {
+        self.regex = pattern
+            .as_deref()
+            .filter(|p| !p.is_empty())
+            .and_then(|p| Regex::new(p).ok());
+        self.pattern = pattern;
+    }
+
+    /// finds the closest match after (or before, going backwards) `from`, wrapping around the document
+    /// also returns whether the search wrapped
+    pub fn find(&self, docu: &Document, from: Position, forward: bool) -> Option<(Position, bool)> {
+        let regex = self.regex.as_ref()?;
+        let n_lines = docu.n_lines();
+        // visiting the starting line twice covers the part of it on the other side of the cursor after wrapping
+        for step in 0..=n_lines {
+            let (line, wrapped) = if forward {
+                let line = from.line + step;
+                (line % n_lines, line >= n_lines)
+            } else {
+                ((from.line + n_lines * 2 - step) % n_lines, step > from.line)
+            };
+            let matches = line_matches(&docu.line(line), regex);
+            let found = if forward {
+                matches
+                    .iter()
+                    .map(|m| m.start)
+                    .find(|&col| step > 0 || col > from.col)
+            } else {
+                matches
+                    .iter()
+                    .rev()
+                    .map(|m| m.start)
+                    .find(|&col| step > 0 || col < from.col)
+            };
+            if let Some(col) = found {
+                return Some((Position::new(line, col), wrapped));
+            }
+        }
+        None
+    }
+}
+
+/// every match of `regex` in `line` as grapheme ranges, empty matches are left out since there's nothing to show
+pub fn line_matches(line: &str, regex: &Regex) -> Vec<Range<usize>> {
+    let mut matches = Vec::new();
+    let mut col = 0;
+    let mut last_byte = 0;
+    for m in regex.find_iter(line).filter(|m| !m.is_empty()) {
+        col += line[last_byte..m.start()].graphemes(true).count();
+        let len = m.as_str().graphemes(true).count();
+        matches.push(col..col + len);
+        col += len;
+        last_byte = m.end();
+    }
+    matches
+}
@@ -0,0 +1,153 @@
+//! What the visual modes select, worked out from where visual mode started and the cursor.
+//! Columns are graphemes like the cursor, and both ends of a selection are included like in vim.
+
+use crate::document::{Document, Position};
+use crate::mode::Mode;
+use crate::register::{Register, RegisterKind};
+
+#[derive(Clone, Copy)]
+pub enum Selection {
+    Chars {
+        start: Position,
+        end: Position,
+    },
+    Lines {
+        first: usize,
+        last: usize,
+    },
+    Block {
+        first: usize,
+        last: usize,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl Selection {
+    /// the selection between `anchor` and `cursor` in the given visual mode, none outside of visual modes
+    pub fn new(mode: Mode, anchor: Position, cursor: Position) -> Option<Self> {
+        let (start, end) = (anchor.min(cursor), anchor.max(cursor));
+        match mode {
+            Mode::Visual => Some(Selection::Chars { start, end }),
+            Mode::VisualLine => Some(Selection::Lines {
+                first: start.line,
+                last: end.line,
+            }),
+            Mode::VisualBlock => Some(Selection::Block {
+                first: start.line,
+                last: end.line,
+                left: anchor.col.min(cursor.col),
+                right: anchor.col.max(cursor.col),
+            }),
+            _ => None,
+        }
+    }
+
+    /// the characters from `start` up to but not including `end`, none if that's nothing
+    pub fn chars_between(docu: &Document, start: Position, end: Position) -> Option<Self> {
+        if end <= start {
+            return None;
+        }
+        let end = if end.col > 0 {
+            Position::new(end.line, end.col - 1)
+        } else {
+            // ending at the start of a line takes the line break before it
+            Position::new(end.line - 1, docu.line_len(end.line - 1))
+        };
+        Some(Selection::Chars { start, end })
+    }
+
+    /// whether the grapheme at `col` is selected, a column past the end of a line stands for its line break
+    pub fn contains(&self, line: usize, col: usize) -> bool {
+        match *self {
+            Selection::Chars { start, end } => {
+                Position::new(line, col) >= start && Position::new(line, col) <= end
+            }
+            Selection::Lines { first, last } => (first..=last).contains(&line),
+            Selection::Block {
+                first,
+                last,
+                left,
+                right,
+            } => (first..=last).contains(&line) && (left..=right).contains(&col),
+        }
+    }
+
+    pub fn first_line(&self) -> usize {
+        match *self {
+            Selection::Chars { start, .. } => start.line,
+            Selection::Lines { first, .. } | Selection::Block { first, .. } => first,
+        }
+    }
+
+    pub fn last_line(&self) -> usize {
+        match *self {
+            Selection::Chars { end, .. } => end.line,
+            Selection::Lines { last, .. } | Selection::Block { last, .. } => last,
+        }
+    }
+
+    /// the top left corner of the selection, where the cursor goes after operating on it
+    pub fn start(&self) -> Position {
+        match *self {
+            Selection::Chars { start, .. } => start,
+            Selection::Lines { first, .. } => Position::new(first, 0),
+            Selection::Block { first, left, .. } => Position::new(first, left),
+        }
+    }
+
+    /// copies the selected text, block selections come out one line per row
+    pub fn yank(&self, docu: &Document) -> Register {
+        match *self {
+            Selection::Chars { start, end } => Register {
+                text: docu.text_range(start, Self::after(docu, end)),
+                kind: RegisterKind::Chars,
+            },
+            Selection::Lines { first, last } => Register {
+                text: (first..=last).map(|line| docu.line(line) + "\n").collect(),
+                kind: RegisterKind::Lines,
+            },
+            Selection::Block {
+                first,
+                last,
+                left,
+                right,
+            } => Register {
+                text: (first..=last)
+                    .map(|line| {
+                        docu.text_range(Position::new(line, left), Position::new(line, right + 1))
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                kind: RegisterKind::Block,
+            },
+        }
+    }
+
+    /// removes the selected text from the document
+    pub fn delete(&self, docu: &mut Document) {
+        match *self {
+            Selection::Chars { start, end } => docu.delete_range(start, Self::after(docu, end)),
+            Selection::Lines { first, last } => docu.delete_lines(first, last),
+            Selection::Block {
+                first,
+                last,
+                left,
+                right,
+            } => {
+                for line in first..=last {
+                    docu.delete_range(Position::new(line, left), Position::new(line, right + 1));
+                }
+            }
+        }
+    }
+
+    /// the position right after an included end, which is the next line when the end is on a line break
+    fn after(docu: &Document, end: Position) -> Position {
+        if end.col >= docu.line_len(end.line) && end.line + 1 < docu.n_lines() {
+            Position::new(end.line + 1, 0)
+        } else {
+            Position::new(end.line, end.col + 1)
+        }
+    }
+}
@@ -1,8 +1,12 @@
+use crate::file_format::FileFormat;
 use crate::mode::Mode;
 pub struct StatusBar {
     pub file_name: String,
     pub mode: Mode,
     pub has_unsaved_changes: bool,
+    pub read_only: bool,
+    pub is_new: bool,
+    pub message: Option<String>, // shown until the next key press, e.g. save errors
 }
 
 impl StatusBar {
@@ -11,29 +15,46 @@ impl StatusBar {
             file_name,
             mode,
             has_unsaved_changes,
+            read_only: false,
+            is_new: false,
+            message: None,
         }
     }
     pub fn format(
         &self,
         width: u16,
-        unsaved: bool,
-        cursor_y: u16,
-        cursor_x: u16,
-        n_lines: u16,
+        cursor_y: usize,
+        cursor_x: usize,
+        n_lines: usize,
+        file_format: &FileFormat,
+        active: bool, // windows without the cursor only show which file they're on
     ) -> String {
         let mut mode = String::from("│ ");
         mode.push_str(&self.mode.to_string());
         let mut left_side = String::from(" ");
         left_side.push_str(&self.file_name);
-        if unsaved {
+        if self.is_new {
+            left_side.push_str(" [New]");
+        }
+        if self.read_only {
+            left_side.push_str(" [RO]");
+        }
+        if self.has_unsaved_changes {
             left_side.push_str(" [+]");
         } else {
             left_side.push_str("    ");
         }
-        left_side.push_str(&mode);
+        if active {
+            left_side.push_str(&mode);
+        }
+        if active && let Some(message) = &self.message {
+            left_side.push_str(" │ ");
+            left_side.push_str(message);
+        }
 
         let right_side = format!(
-            "{} │ {}/{}  ",
+            "{} │ {} │ {}/{}  ",
+            file_format,
             cursor_x,
             if cursor_y <= n_lines {
                 cursor_y + 1
@@ -43,11 +64,15 @@ impl StatusBar {
             n_lines
         );
 
+        // long messages get cut off instead of pushing the cursor position off screen
+        let left_width = (width as usize).saturating_sub(right_side.len() + 2);
+        let left_side: String = left_side.chars().take(left_width).collect();
+
         let status_bar = format!(
             "{:<width$}│{:>right_width$}",
             left_side,
             right_side,
-            width = width as usize - right_side.len() - 2,
+            width = left_width,
             right_width = right_side.len() - 1
         );
         status_bar
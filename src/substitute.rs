@@ -0,0 +1,301 @@
+//! The `:s/pattern/replacement/flags` command.
+//! Patterns use the regex crate's syntax, replacements use vim's (`&`, `\1`, `\r`...) and are translated.
+
+use crate::document::{Document, Position};
+use regex::Regex;
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// the lines a command applies to, line numbers are 1-based like on screen
+#[derive(Clone, Copy)]
+pub enum LineRange {
+    Current,
+    All,
+    Lines(usize, usize),
+}
+
+pub struct SubstituteArgs {
+    pub range: LineRange,
+    pub pattern: String, // empty means the last search pattern
+    pub replacement: String,
+    pub global: bool,
+    pub ignore_case: bool,
+    pub confirm: bool,
+}
+
+/// a substitution in progress, matches are found and replaced one at a time so they can be confirmed
+pub struct Substitution {
+    regex: Regex,
+    replacement: String, // in the regex crate's syntax
+    global: bool,
+    pub confirm: bool,
+    pub prompt: String, // asked for each match when confirming
+    line: usize,
+    end_line: usize,
+    byte: usize,                   // where on `line` to look for the next match
+    current: Option<Range<usize>>, // bytes of the match on `line` waiting to be replaced or skipped
+    last_changed_line: Option<usize>,
+    pub last_replaced: Option<Position>,
+    pub substitutions: usize,
+    pub lines: usize,
+}
+
+impl LineRange {
+    /// splits a range like `%` or `3,7` off the front of a command
+    pub fn parse(input: &str) -> Result<(LineRange, &str), String> {
+        if let Some(rest) = input.strip_prefix('%') {
+            return Ok((LineRange::All, rest));
+        }
+        let digits = input
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(input.len());
+        if digits == 0 {
+            return Ok((LineRange::Current, input));
+        }
+        let start: usize = input[..digits].parse().map_err(|_| "Invalid range")?;
+        let Some(rest) = input[digits..].strip_prefix(',') else {
+            return Ok((LineRange::Lines(start, start), &input[digits..]));
+        };
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let end: usize = rest[..digits].parse().map_err(|_| "Invalid range")?;
+        if end < start {
+            return Err("Backwards range given".to_string());
+        }
+        Ok((LineRange::Lines(start, end), &rest[digits..]))
+    }
+
+    /// the 0-based, inclusive lines this range covers
+    pub fn resolve(self, cursor_line: usize, n_lines: usize) -> (usize, usize) {
+        match self {
+            LineRange::Current => (cursor_line, cursor_line),
+            LineRange::All => (0, n_lines - 1),
+            LineRange::Lines(start, end) => (
+                start.saturating_sub(1).min(n_lines - 1),
+                end.saturating_sub(1).min(n_lines - 1),
+            ),
+        }
+    }
+}
+
+impl SubstituteArgs {
+    /// parses what follows the `s`: a delimiter, the pattern, the replacement and flags, e.g. `/foo/bar/g`
+    /// the delimiter can be any punctuation and can be escaped with a backslash inside the pattern or replacement
+    pub fn parse(range: LineRange, input: &str) -> Result<Self, String> {
+        let mut chars = input.chars();
+        let delimiter = chars
+            .next()
+            .filter(|c| !c.is_alphanumeric() && !c.is_whitespace() && *c != '\\')
+            .ok_or("E146: Regular expressions can't be delimited by letters")?;
+
+        let mut fields = vec![String::new()];
+        let mut escaped = false;
+        for c in chars {
+            let n_fields = fields.len();
+            let field = fields.last_mut().unwrap();
+            if escaped {
+                // an escaped delimiter stands for itself, anything else keeps its backslash for later
+                if c != delimiter {
+                    field.push('\\');
+                }
+                field.push(c);
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == delimiter && n_fields < 3 {
+                fields.push(String::new());
+            } else {
+                field.push(c);
+            }
+        }
+        if escaped {
+            fields.last_mut().unwrap().push('\\');
+        }
+
+        let mut fields = fields.into_iter();
+        let pattern = fields.next().unwrap_or_default();
+        let replacement = fields.next().unwrap_or_default();
+        let flags = fields.next().unwrap_or_default();
+        let mut args = SubstituteArgs {
+            range,
+            pattern,
+            replacement,
+            global: false,
+            ignore_case: false,
+            confirm: false,
+        };
+        for flag in flags.trim().chars() {
+            match flag {
+                'g' => args.global = true,
+                'i' => args.ignore_case = true,
+                'I' => args.ignore_case = false,
+                'c' => args.confirm = true,
+                _ => return Err(format!("Trailing characters: {flags}")),
+            }
+        }
+        Ok(args)
+    }
+}
+
+impl Substitution {
+    pub fn new(args: &SubstituteArgs, first_line: usize, last_line: usize) -> Result<Self, String> {
+        let pattern = if args.ignore_case {
+            format!("(?i){}", args.pattern)
+        } else {
+            args.pattern.clone()
+        };
+        let regex = Regex::new(&pattern).map_err(|e| format!("Invalid pattern: {e}"))?;
+        Ok(Substitution {
+            regex,
+            replacement: translate_replacement(&args.replacement),
+            global: args.global,
+            confirm: args.confirm,
+            prompt: format!("replace with {} (y/n/a/q/l)?", args.replacement),
+            line: first_line,
+            end_line: last_line,
+            byte: 0,
+            current: None,
+            last_changed_line: None,
+            last_replaced: None,
+            substitutions: 0,
+            lines: 0,
+        })
+    }
+
+    /// looks for the next match in range, returns false once there are none left
+    pub fn find_next(&mut self, docu: &Document) -> bool {
+        while self.line <= self.end_line && self.line < docu.n_lines() {
+            let line_str = docu.line(self.line);
+            if self.byte <= line_str.len()
+                && let Some(m) = self.regex.find_at(&line_str, self.byte)
+            {
+                self.current = Some(m.range());
+                return true;
+            }
+            self.next_line();
+        }
+        self.current = None;
+        false
+    }
+
+    /// the match waiting to be confirmed, as its line and grapheme columns
+    pub fn current_match(&self, docu: &Document) -> Option<(usize, Range<usize>)> {
+        let range = self.current.clone()?;
+        let line_str = docu.line(self.line);
+        Some((
+            self.line,
+            col_of_byte(&line_str, range.start)..col_of_byte(&line_str, range.end),
+        ))
+    }
+
+    pub fn replace_current(&mut self, docu: &mut Document) {
+        let Some(range) = self.current.take() else {
+            return;
+        };
+        let line_str = docu.line(self.line);
+        let Some(captures) = self.regex.captures_at(&line_str, self.byte) else {
+            return;
+        };
+        let mut replacement = String::new();
+        captures.expand(&self.replacement, &mut replacement);
+
+        let start = Position::new(self.line, col_of_byte(&line_str, range.start));
+        let end = Position::new(self.line, col_of_byte(&line_str, range.end));
+        docu.delete_range(start, end);
+        let after = docu.insert_str(start, &replacement);
+
+        self.substitutions += 1;
+        if self.last_changed_line != Some(self.line) {
+            self.lines += 1;
+        }
+        self.last_changed_line = Some(after.line);
+        self.last_replaced = Some(start);
+        // line breaks in the replacement push the rest of the range down
+        self.end_line += after.line - self.line;
+        self.line = after.line;
+        let after_byte = byte_of_col(&docu.line(after.line), after.col);
+        self.advance(after_byte, range.is_empty(), docu);
+    }
+
+    pub fn skip_current(&mut self, docu: &Document) {
+        if let Some(range) = self.current.take() {
+            self.advance(range.end, range.is_empty(), docu);
+        }
+    }
+
+    /// replaces every remaining match without asking
+    pub fn replace_all(&mut self, docu: &mut Document) {
+        while self.find_next(docu) {
+            self.replace_current(docu);
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        format!(
+            "{} substitution{} on {} line{}",
+            self.substitutions,
+            plural(self.substitutions),
+            self.lines,
+            plural(self.lines)
+        )
+    }
+
+    fn advance(&mut self, byte: usize, empty_match: bool, docu: &Document) {
+        if !self.global {
+            self.next_line();
+            return;
+        }
+        self.byte = byte;
+        // step over one character after an empty match so it isn't found again
+        if empty_match {
+            let line_str = docu.line(self.line);
+            match line_str[byte.min(line_str.len())..].chars().next() {
+                Some(c) => self.byte += c.len_utf8(),
+                None => self.next_line(),
+            }
+        }
+    }
+
+    fn next_line(&mut self) {
+        self.line += 1;
+        self.byte = 0;
+    }
+}
+
+/// translates a vim replacement string into the regex crate's expansion syntax
+fn translate_replacement(replacement: &str) -> String {
+    let mut out = String::new();
+    let mut chars = replacement.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '&' => out.push_str("${0}"),
+            '$' => out.push_str("$$"),
+            '\\' => match chars.next() {
+                Some(digit @ '0'..='9') => {
+                    out.push_str("${");
+                    out.push(digit);
+                    out.push('}');
+                }
+                Some('r' | 'n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('$') => out.push_str("$$"),
+                Some(other) => out.push(other),
+                None => out.push('\\'),
+            },
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn col_of_byte(line: &str, byte: usize) -> usize {
+    line[..byte.min(line.len())].graphemes(true).count()
+}
+
+fn byte_of_col(line: &str, col: usize) -> usize {
+    line.grapheme_indices(true)
+        .nth(col)
+        .map_or(line.len(), |(idx, _)| idx)
+}
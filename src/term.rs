@@ -1,10 +1,13 @@
 use crossterm::{
-    QueueableCommand,
+    ExecutableCommand, QueueableCommand,
+    clipboard::CopyToClipboard,
     cursor::MoveTo,
+    event::{DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture},
     style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
-    terminal::{Clear, ClearType},
+    terminal::{BeginSynchronizedUpdate, Clear, ClearType, EndSynchronizedUpdate},
 };
 use std::{
+    env,
     fmt::Display,
     io::{Error, Write, stdout},
 };
@@ -12,14 +15,52 @@ use std::{
 pub struct Terminal {
     pub width: u16,
     pub height: u16,
+    pub synchronized_output: bool, // frames can be wrapped in synchronized updates, see `supports_synchronized_output`
 }
 
 impl Terminal {
     pub fn build() -> Result<Terminal, Error> {
         let (width, height) = crossterm::terminal::size()?;
         crossterm::terminal::enable_raw_mode()?;
+        // pasted text arrives as one event instead of a key press per character
+        stdout().execute(EnableBracketedPaste)?;
+        stdout().execute(EnableMouseCapture)?;
         Self::clear()?;
-        Ok(Terminal { width, height })
+        Ok(Terminal {
+            width,
+            height,
+            synchronized_output: Self::supports_synchronized_output(),
+        })
+    }
+
+    /// whether the terminal understands synchronized updates (mode 2026) and shows a frame only once it's complete
+    /// asking the terminal would mean reading its answer before crossterm does, which can't be done without
+    /// getting in the way of the key presses, so the terminals known to support it are recognized by their environment
+    fn supports_synchronized_output() -> bool {
+        let var = |name: &str| env::var(name).unwrap_or_default();
+        let term = var("TERM");
+        if term == "dumb" || term == "linux" {
+            return false;
+        }
+        let known_term = [
+            "kitty",
+            "alacritty",
+            "foot",
+            "wezterm",
+            "ghostty",
+            "contour",
+            "rio",
+        ]
+        .iter()
+        .any(|name| term.contains(name));
+        let known_program = matches!(
+            var("TERM_PROGRAM").as_str(),
+            "tmux" | "WezTerm" | "iTerm.app" | "ghostty" | "vscode" | "rio"
+        );
+        let known_session = ["TMUX", "KITTY_WINDOW_ID", "WEZTERM_PANE", "WT_SESSION"]
+            .iter()
+            .any(|name| env::var_os(name).is_some());
+        known_term || known_program || known_session
     }
 
     pub fn clear() -> Result<(), Error> {
@@ -55,8 +96,14 @@ impl Terminal {
         Ok(())
     }
 
-    pub fn clear_current_line() -> Result<(), Error> {
-        stdout().queue(Clear(ClearType::CurrentLine))?;
+    /// the terminal holds off on showing anything until `end_synchronized_update`
+    pub fn begin_synchronized_update() -> Result<(), Error> {
+        stdout().queue(BeginSynchronizedUpdate)?;
+        Ok(())
+    }
+
+    pub fn end_synchronized_update() -> Result<(), Error> {
+        stdout().queue(EndSynchronizedUpdate)?;
         Ok(())
     }
 
@@ -74,4 +121,24 @@ impl Terminal {
         stdout().queue(crossterm::cursor::Show)?;
         Ok(())
     }
+
+    /// asks the terminal to put `text` on the system clipboard (or the primary selection) with OSC 52,
+    /// which also works over ssh, terminals that don't support it simply ignore it
+    pub fn copy_to_clipboard(text: &str, primary: bool) -> Result<(), Error> {
+        if primary {
+            stdout().queue(CopyToClipboard::to_primary_from(text))?;
+        } else {
+            stdout().queue(CopyToClipboard::to_clipboard_from(text))?;
+        }
+        Self::flush()
+    }
+}
+
+impl Drop for Terminal {
+    /// hands the terminal back the way it was before `build`
+    fn drop(&mut self) {
+        let _ = stdout().execute(DisableMouseCapture);
+        let _ = stdout().execute(DisableBracketedPaste);
+        let _ = crossterm::terminal::disable_raw_mode();
+    }
 }
@@ -0,0 +1,51 @@
+//! Text objects like `iw`, `a"` and `i(`, typed after an operator or in visual mode to act on a structured region.
+//! The regions themselves are worked out by `Document`.
+
+use crate::document::{Document, Position};
+
+#[derive(Clone, Copy)]
+pub enum TextObject {
+    Word,
+    Quote(char),
+    Bracket(char, char),
+    Tag,
+    Paragraph,
+}
+
+/// the text a text object covers
+pub enum ObjectRange {
+    Chars(Position, Position), // the end isn't included, `i"` on `""` covers nothing
+    Lines(usize, usize),
+}
+
+impl TextObject {
+    /// the object named by the key typed after `i` or `a`
+    pub fn from_char(c: char) -> Option<Self> {
+        match c {
+            'w' => Some(TextObject::Word),
+            '"' | '\'' | '`' => Some(TextObject::Quote(c)),
+            '(' | ')' | 'b' => Some(TextObject::Bracket('(', ')')),
+            '{' | '}' | 'B' => Some(TextObject::Bracket('{', '}')),
+            '[' | ']' => Some(TextObject::Bracket('[', ']')),
+            '<' | '>' => Some(TextObject::Bracket('<', '>')),
+            't' => Some(TextObject::Tag),
+            'p' => Some(TextObject::Paragraph),
+            _ => None,
+        }
+    }
+
+    /// what the object covers around `pos`, `around` is true for the `a` variants
+    pub fn find(self, docu: &Document, pos: Position, around: bool) -> Option<ObjectRange> {
+        let (start, end) = match self {
+            TextObject::Word => docu.word_object(pos, around),
+            TextObject::Quote(quote) => docu.quote_object(pos, quote, around)?,
+            TextObject::Bracket(open, close) => docu.bracket_object(pos, open, close, around)?,
+            TextObject::Tag => docu.tag_object(pos, around)?,
+            TextObject::Paragraph => {
+                let (first, last) = docu.paragraph_object(pos.line, around);
+                return Some(ObjectRange::Lines(first, last));
+            }
+        };
+        Some(ObjectRange::Chars(start, end))
+    }
+}
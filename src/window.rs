@@ -0,0 +1,426 @@
+//! Windows: views into buffers sharing the screen, which is divided by splitting it across or down.
+
+use crate::document::Position;
+
+const MIN_HEIGHT: u16 = 2; // one line of text and the status line
+const MIN_WIDTH: u16 = 8;
+
+/// an area of the screen, in cells
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+pub struct Window {
+    pub id: usize,
+    pub buffer: usize, // the number of the buffer shown
+    pub cursor: Position,
+    pub top_offset: usize,
+    pub left_offset: usize,
+    pub rect: Rect, // where the window is on screen, its status line is the last row
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Down,
+    Up,
+    Right,
+}
+
+/// how the screen is divided, a split holds its children with the rows or columns each one gets
+enum Layout {
+    Window(usize),
+    Split {
+        vertical: bool, // side by side, with a separator column between the children
+        children: Vec<(Layout, u16)>,
+    },
+}
+
+/// every window of the screen, the current window's cursor and offsets live in the editor while it's current
+pub struct Windows {
+    layout: Layout,
+    list: Vec<Window>,
+    pub current: usize, // id of the window with the cursor
+    next_id: usize,
+    pub separators: Vec<Rect>, // the columns between windows that are side by side
+}
+
+impl Rect {
+    pub fn contains(self, x: u16, y: u16) -> bool {
+        (self.x..self.x + self.width).contains(&x) && (self.y..self.y + self.height).contains(&y)
+    }
+}
+
+impl Windows {
+    /// a single window filling the screen
+    pub fn new(buffer: usize) -> Self {
+        Windows {
+            layout: Layout::Window(0),
+            list: vec![Window {
+                id: 0,
+                buffer,
+                cursor: Position::default(),
+                top_offset: 0,
+                left_offset: 0,
+                rect: Rect::default(),
+            }],
+            current: 0,
+            next_id: 1,
+            separators: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Window> {
+        self.list.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Window> {
+        self.list.iter_mut()
+    }
+
+    pub fn current(&self) -> &Window {
+        self.get(self.current)
+    }
+
+    pub fn current_mut(&mut self) -> &mut Window {
+        let current = self.current;
+        self.list
+            .iter_mut()
+            .find(|w| w.id == current)
+            .expect("the current window is in the list")
+    }
+
+    pub fn get(&self, id: usize) -> &Window {
+        self.list
+            .iter()
+            .find(|w| w.id == id)
+            .expect("window ids come from the list")
+    }
+
+    /// the window at a screen cell, status lines included
+    pub fn at(&self, x: u16, y: u16) -> Option<usize> {
+        self.list
+            .iter()
+            .find(|w| w.rect.contains(x, y))
+            .map(|w| w.id)
+    }
+
+    /// splits the current window in two showing the same thing, the new half comes first and becomes current
+    /// returns false when the window is too small to split
+    pub fn split(&mut self, vertical: bool) -> bool {
+        let current = self.current();
+        // both halves need the room resizing leaves a window, side by side there's also the separator between them
+        let fits = if vertical {
+            current.rect.width > 2 * MIN_WIDTH
+        } else {
+            current.rect.height >= 2 * MIN_HEIGHT
+        };
+        if !fits {
+            return false;
+        }
+        let window = Window {
+            id: self.next_id,
+            buffer: current.buffer,
+            cursor: current.cursor,
+            top_offset: current.top_offset,
+            left_offset: current.left_offset,
+            rect: current.rect,
+        };
+        let size = if vertical {
+            current.rect.width
+        } else {
+            current.rect.height
+        };
+        self.layout.split(self.current, window.id, vertical, size);
+        self.current = window.id;
+        self.next_id += 1;
+        self.list.push(window);
+        true
+    }
+
+    /// closes window `id` unless it's the last one, the window before it takes its place and becomes current
+    pub fn close(&mut self, id: usize) -> bool {
+        if self.list.len() == 1 {
+            return false;
+        }
+        let order = self.layout.ids();
+        let idx = order.iter().position(|&w| w == id).unwrap_or(0);
+        let next = if idx == 0 { order[1] } else { order[idx - 1] };
+        self.layout.remove(id);
+        self.list.retain(|w| w.id != id);
+        if self.current == id {
+            self.current = next;
+        }
+        true
+    }
+
+    /// closes every window but the current one
+    pub fn only(&mut self) {
+        let current = self.current;
+        self.layout = Layout::Window(current);
+        self.list.retain(|w| w.id == current);
+    }
+
+    /// the window after or before the current one, going around
+    pub fn cycle(&self, forward: bool, count: usize) -> usize {
+        let order = self.layout.ids();
+        let idx = order.iter().position(|&w| w == self.current).unwrap_or(0);
+        let steps = count % order.len();
+        let idx = if forward {
+            (idx + steps) % order.len()
+        } else {
+            (idx + order.len() - steps) % order.len()
+        };
+        order[idx]
+    }
+
+    /// the window next to the current one on the screen, preferring the one level with its top left corner
+    pub fn neighbor(&self, direction: Direction) -> Option<usize> {
+        let from = self.current().rect;
+        let overlaps = |start: u16, len: u16, other_start: u16, other_len: u16| {
+            start < other_start + other_len && other_start < start + len
+        };
+        self.list
+            .iter()
+            .filter(|w| {
+                let r = w.rect;
+                match direction {
+                    Direction::Left => {
+                        r.x + r.width + 1 == from.x && overlaps(r.y, r.height, from.y, from.height)
+                    }
+                    Direction::Right => {
+                        from.x + from.width + 1 == r.x
+                            && overlaps(r.y, r.height, from.y, from.height)
+                    }
+                    Direction::Up => {
+                        r.y + r.height == from.y && overlaps(r.x, r.width, from.x, from.width)
+                    }
+                    Direction::Down => {
+                        from.y + from.height == r.y && overlaps(r.x, r.width, from.x, from.width)
+                    }
+                }
+            })
+            .min_by_key(|w| match direction {
+                Direction::Left | Direction::Right => {
+                    (w.rect.y > from.y, w.rect.y.abs_diff(from.y))
+                }
+                Direction::Up | Direction::Down => (w.rect.x > from.x, w.rect.x.abs_diff(from.x)),
+            })
+            .map(|w| w.id)
+    }
+
+    /// makes the current window `amount` rows (or columns) bigger or smaller, the window next to it makes up the difference
+    pub fn resize(&mut self, vertical: bool, amount: u16, grow: bool) {
+        self.layout.resize(self.current, vertical, amount, grow);
+    }
+
+    /// gives every window the same size
+    pub fn equalize(&mut self) {
+        self.layout.equalize();
+    }
+
+    /// works out where every window goes within `area`
+    pub fn arrange(&mut self, area: Rect) {
+        let mut rects = Vec::new();
+        self.separators.clear();
+        self.layout.arrange(area, &mut rects, &mut self.separators);
+        for (id, rect) in rects {
+            if let Some(window) = self.list.iter_mut().find(|w| w.id == id) {
+                window.rect = rect;
+            }
+        }
+    }
+}
+
+impl Layout {
+    fn contains(&self, id: usize) -> bool {
+        match self {
+            Layout::Window(window) => *window == id,
+            Layout::Split { children, .. } => children.iter().any(|(child, _)| child.contains(id)),
+        }
+    }
+
+    /// the windows from the top left to the bottom right, the order `Ctrl-w w` goes in
+    fn ids(&self) -> Vec<usize> {
+        match self {
+            Layout::Window(id) => vec![*id],
+            Layout::Split { children, .. } => {
+                children.iter().flat_map(|(child, _)| child.ids()).collect()
+            }
+        }
+    }
+
+    /// puts window `new` before `target`, `size` being what `target` had in the direction of the split
+    fn split(&mut self, target: usize, new: usize, vertical: bool, size: u16) -> bool {
+        match self {
+            Layout::Window(id) if *id == target => {
+                *self = Layout::Split {
+                    vertical,
+                    children: vec![
+                        (Layout::Window(new), size / 2),
+                        (Layout::Window(target), size - size / 2),
+                    ],
+                };
+                true
+            }
+            Layout::Window(_) => false,
+            Layout::Split {
+                vertical: direction,
+                children,
+            } => {
+                // splitting in the same direction again adds to this split instead of nesting another one
+                if *direction == vertical
+                    && let Some(idx) = children
+                        .iter()
+                        .position(|(child, _)| matches!(child, Layout::Window(id) if *id == target))
+                {
+                    let size = children[idx].1;
+                    children[idx].1 = size - size / 2;
+                    children.insert(idx, (Layout::Window(new), size / 2));
+                    return true;
+                }
+                children
+                    .iter_mut()
+                    .any(|(child, _)| child.split(target, new, vertical, size))
+            }
+        }
+    }
+
+    fn remove(&mut self, id: usize) -> bool {
+        let Layout::Split { children, .. } = self else {
+            return false;
+        };
+        let Some(idx) = children
+            .iter()
+            .position(|(child, _)| matches!(child, Layout::Window(window) if *window == id))
+        else {
+            return children.iter_mut().any(|(child, _)| child.remove(id));
+        };
+        let (_, size) = children.remove(idx);
+        let neighbor = idx.saturating_sub(1);
+        children[neighbor].1 += size;
+        if children.len() == 1
+            && let Some((only, _)) = children.pop()
+        {
+            *self = only;
+        }
+        true
+    }
+
+    fn resize(&mut self, id: usize, vertical: bool, amount: u16, grow: bool) -> bool {
+        let Layout::Split {
+            vertical: direction,
+            children,
+        } = self
+        else {
+            return false;
+        };
+        // the split closest to the window gets resized
+        if children
+            .iter_mut()
+            .any(|(child, _)| child.resize(id, vertical, amount, grow))
+        {
+            return true;
+        }
+        if *direction != vertical {
+            return false;
+        }
+        let Some(idx) = children.iter().position(|(child, _)| child.contains(id)) else {
+            return false;
+        };
+        let neighbor = if idx + 1 < children.len() {
+            idx + 1
+        } else {
+            idx - 1
+        };
+        let (from, to) = if grow {
+            (neighbor, idx)
+        } else {
+            (idx, neighbor)
+        };
+        let min = if vertical { MIN_WIDTH } else { MIN_HEIGHT };
+        let amount = amount.min(children[from].1.saturating_sub(min));
+        children[from].1 -= amount;
+        children[to].1 += amount;
+        true
+    }
+
+    fn equalize(&mut self) {
+        if let Layout::Split { children, .. } = self {
+            for (child, size) in children {
+                *size = 1;
+                child.equalize();
+            }
+        }
+    }
+
+    fn arrange(&mut self, area: Rect, rects: &mut Vec<(usize, Rect)>, separators: &mut Vec<Rect>) {
+        let Layout::Split { vertical, children } = self else {
+            if let Layout::Window(id) = self {
+                rects.push((*id, area));
+            }
+            return;
+        };
+        let vertical = *vertical;
+        let n_children = children.len() as u16;
+        let total = if vertical {
+            area.width.saturating_sub(n_children - 1)
+        } else {
+            area.height
+        };
+        fit(children, total);
+        let mut offset = 0;
+        let last = children.len() - 1;
+        for (idx, (child, size)) in children.iter_mut().enumerate() {
+            let rect = if vertical {
+                Rect {
+                    x: area.x + offset,
+                    width: *size,
+                    ..area
+                }
+            } else {
+                Rect {
+                    y: area.y + offset,
+                    height: *size,
+                    ..area
+                }
+            };
+            child.arrange(rect, rects, separators);
+            offset += *size;
+            if vertical && idx < last {
+                separators.push(Rect {
+                    x: area.x + offset,
+                    width: 1,
+                    ..area
+                });
+                offset += 1;
+            }
+        }
+    }
+}
+
+/// scales the sizes of the children to add up to `total`, keeping their proportions
+fn fit(children: &mut [(Layout, u16)], total: u16) {
+    let sum: u32 = children.iter().map(|(_, size)| u32::from(*size)).sum();
+    if sum == u32::from(total) {
+        return;
+    }
+    let mut used = 0;
+    let last = children.len() - 1;
+    for (idx, (_, size)) in children.iter_mut().enumerate() {
+        *size = if idx == last {
+            total.saturating_sub(used)
+        } else {
+            (u32::from(*size) * u32::from(total) / sum.max(1)) as u16
+        };
+        used += *size;
+    }
+}
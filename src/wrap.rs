@@ -0,0 +1,84 @@
+//! Soft wrapping: with `:set wrap` lines too long for the window carry on in the rows below instead of
+//! scrolling sideways, breaking after a space where there is one.
+
+use crate::document::Document;
+use crate::screen::display_width;
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// a row of a window showing text, `cols` are the graphemes of `line` on it
+pub struct ScreenRow {
+    pub line: usize,
+    pub cols: Range<usize>,
+}
+
+/// the graphemes each row of `line` starts at when it's wrapped to `width` columns
+pub fn row_starts(line: &str, width: usize) -> Vec<usize> {
+    let graphemes: Vec<(usize, bool)> = line
+        .graphemes(true)
+        .map(|g| (display_width(g), g.chars().all(char::is_whitespace)))
+        .collect();
+    let mut starts = vec![0];
+    let mut used = 0; // columns taken on the current row
+    let mut after_space = None; // the grapheme after the last space on the current row
+    for (idx, &(grapheme_width, is_space)) in graphemes.iter().enumerate() {
+        while used > 0 && used + grapheme_width > width {
+            // the word that doesn't fit moves down whole, unless nothing came before it on the row
+            let start = match after_space {
+                Some(after) if !is_space => after,
+                _ => idx,
+            };
+            starts.push(start);
+            used = graphemes[start..idx].iter().map(|(w, _)| w).sum();
+            after_space = None;
+        }
+        used += grapheme_width;
+        if is_space {
+            after_space = Some(idx + 1);
+        }
+    }
+    starts
+}
+
+/// which of the rows starting at `starts` column `col` is on
+pub fn row_of(starts: &[usize], col: usize) -> usize {
+    starts.partition_point(|&start| start <= col) - 1
+}
+
+/// what the rows of a window show from line `top_offset` on, until `height` rows are filled or the document ends
+/// without a `wrap_width` every line gets a single row showing it from `left_offset` on, with one the top line
+/// starts at its row holding `left_offset`
+pub fn screen_rows(
+    docu: &Document,
+    top_offset: usize,
+    left_offset: usize,
+    wrap_width: Option<usize>,
+    height: usize,
+) -> Vec<ScreenRow> {
+    let mut rows = Vec::new();
+    for line in top_offset..docu.n_lines() {
+        if rows.len() >= height {
+            break;
+        }
+        let Some(width) = wrap_width else {
+            rows.push(ScreenRow {
+                line,
+                cols: left_offset..usize::MAX,
+            });
+            continue;
+        };
+        let starts = row_starts(&docu.line(line), width);
+        let ends = starts.iter().skip(1).copied().chain([usize::MAX]);
+        for (start, end) in starts.iter().copied().zip(ends) {
+            if line == top_offset && end <= left_offset {
+                continue;
+            }
+            rows.push(ScreenRow {
+                line,
+                cols: start..end,
+            });
+        }
+    }
+    rows.truncate(height);
+    rows
+}